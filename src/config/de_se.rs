@@ -1,14 +1,66 @@
-use super::{Point, Rectangle};
+use super::{Point, Problem, Rectangle};
+use crate::util::pdf::Size;
 use serde_derive::Deserialize;
+use serde_hjson::Value;
 use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize)]
 pub struct StyleJson {
     pub colors: Vec<String>,
     pub font: String,
-    pub margin: Rectangle<f64>,
+    #[serde(rename = "codeFont", default = "default_code_font")]
+    pub code_font: String,
+    /// fonts used in this order for chars the font can't render
+    #[serde(rename = "fallbackFonts", default)]
+    pub fallback_fonts: Vec<String>,
+    /// font files and directories, relative to the style
+    #[serde(default)]
+    pub fonts: Vec<PathBuf>,
+    /// a preset like "16:9" or the size with a unit, ex. "297x210mm"
+    #[serde(default)]
+    pub size: Option<String>,
+    #[serde(default)]
+    pub dpi: Option<u16>,
+    pub margin: Rectangle<Value>,
     #[serde(rename = "lineSpace")]
     pub line_spacing: f64,
+    #[serde(rename = "paragraphSpace", default)]
+    pub paragraph_spacing: Option<f64>,
+    #[serde(rename = "itemSpace", default)]
+    pub item_spacing: Option<f64>,
+    /// one entry per nesting level
+    #[serde(default)]
+    pub list: Vec<ListLevelJson>,
+    /// two letter code of the language used to hyphenate words
+    #[serde(default)]
+    pub hyphenate: Option<String>,
+}
+
+/// the look of a list level, everything not given
+/// is taken from the default of that level
+#[derive(Debug, Deserialize)]
+pub struct ListLevelJson {
+    /// a char or some text, ex. "•"
+    #[serde(default)]
+    pub bullet: Option<String>,
+    /// "square" or "circle", used instead of the bullet
+    #[serde(default)]
+    pub shape: Option<String>,
+    /// path to an image, used instead of the bullet or shape
+    #[serde(default)]
+    pub image: Option<PathBuf>,
+    #[serde(default)]
+    pub color: Option<usize>,
+    #[serde(default)]
+    pub indent: Option<f64>,
+    #[serde(default)]
+    pub spacing: Option<f64>,
+}
+
+fn default_code_font() -> String {
+    String::from("monospace")
 }
 
 /*#[derive(Debug, Deserialize)]
@@ -25,109 +77,768 @@ pub struct SlideTemplate {
 
 #[derive(Debug, Deserialize)]
 pub struct ContentTemplate {
-    pub orig: Point<f64>,
-    pub size: Point<f64>,
+    /// fractions of the drawing area or lengths like "12mm"
+    pub orig: Point<Value>,
+    pub size: Point<Value>,
     pub orientation: String,
+    /// overrides the font family of the style
+    #[serde(default)]
+    pub font: Option<String>,
     #[serde(rename = "fontSize")]
     pub font_size: f32,
+    /// a name like "bold" or a number from 1 to 1000
+    #[serde(rename = "fontWeight", default)]
+    pub font_weight: Option<Value>,
+    /// "normal", "italic" or "oblique"
+    #[serde(rename = "fontStyle", default)]
+    pub font_style: Option<String>,
+    /// a css font-stretch keyword, ex. "condensed"
+    #[serde(rename = "fontStretch", default)]
+    pub font_stretch: Option<String>,
+    /// the index of a color of the style or a color like "%FF0000"
+    #[serde(default)]
+    pub color: Option<Value>,
+    #[serde(rename = "letterSpacing", default)]
+    pub letter_spacing: Option<f64>,
+    #[serde(rename = "lineSpace", default)]
+    pub line_spacing: Option<f64>,
+    #[serde(rename = "paragraphSpace", default)]
+    pub paragraph_spacing: Option<f64>,
+    #[serde(rename = "itemSpace", default)]
+    pub item_spacing: Option<f64>,
+    /// "overflow", "shrink", "clip" or "continue"
+    #[serde(default)]
+    pub fit: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DecorationJson {
-    pub orig: Point<f64>,
-    pub size: Point<f64>,
+    pub orig: Point<Value>,
+    pub size: Point<Value>,
     pub color: usize,
 }
 
-impl From<StyleJson> for super::PresentStyle {
-    fn from(json: StyleJson) -> Self {
-        Self {
-            font: json.font,
-            line_spacing: json.line_spacing,
-            margin: json.margin,
-            colors: json
+/// collects the invalid values of a file together with
+/// their key path, so all of them can be reported at once
+#[derive(Default)]
+struct Validator {
+    /// the keys leading to the current value, ex. ["Title", "template[0]"]
+    keys: Vec<String>,
+    problems: Vec<Problem>,
+}
+
+impl Validator {
+    /// the value if it is valid, otherwise the problem is remembered.
+    /// What is used instead doesn't matter, because the file isn't used
+    fn check<T>(&mut self, key: &str, value: Result<T, &'static str>) -> Option<T> {
+        value
+            .map_err(|message| {
+                let key = self.keys.iter().map(String::as_str).chain(Some(key));
+                self.problems.push(Problem {
+                    key: key.collect::<Vec<_>>().join("."),
+                    message,
+                })
+            })
+            .ok()
+    }
+
+    /// checks the values inside of the key
+    fn nested<R>(&mut self, key: String, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.keys.push(key);
+        let result = f(self);
+        self.keys.pop();
+        result
+    }
+
+    /// the converted value, if nothing was invalid
+    fn finish<T>(self, value: T) -> Result<T, Vec<Problem>> {
+        if self.problems.is_empty() {
+            Ok(value)
+        } else {
+            Err(self.problems)
+        }
+    }
+
+    /// converts the lengths of an area
+    fn area(&mut self, orig: Point<Value>, size: Point<Value>) -> Rectangle<super::Length> {
+        let mut point = |key: &str, p: Point<Value>| Point {
+            x: self.length(&format!("{}.x", key), p.x),
+            y: self.length(&format!("{}.y", key), p.y),
+        };
+
+        Rectangle {
+            orig: point("orig", orig),
+            size: point("size", size),
+        }
+    }
+
+    fn length(&mut self, key: &str, json: Value) -> super::Length {
+        self.check(key, to_length(json))
+            .unwrap_or(super::Length::Relative(0.0))
+    }
+}
+
+impl StyleJson {
+    /// converts the json into the style, every invalid value is returned.
+    /// The paths inside are relative to the directory of the style
+    pub fn into_style(self, dir: &Path) -> Result<super::PresentStyle, Vec<Problem>> {
+        let mut v = Validator::default();
+        let defaults = super::default::default_list_levels();
+        let colors = self.colors.len();
+        let mut list: Vec<super::ListLevel> = self
+            .list
+            .into_iter()
+            .enumerate()
+            .map(|(i, level)| {
+                // levels without a default use the last one
+                let default = defaults[i.min(defaults.len() - 1)].clone();
+                v.nested(format!("list[{}]", i), |v| {
+                    level.into_level(default, colors, dir, v)
+                })
+            })
+            .collect();
+        if list.is_empty() {
+            list = defaults;
+        }
+
+        let style = super::PresentStyle {
+            list,
+            font: self.font,
+            code_font: self.code_font,
+            fallback_fonts: self.fallback_fonts,
+            fonts: self.fonts.iter().map(|font| dir.join(font)).collect(),
+            size: self
+                .size
+                .and_then(|size| v.check("size", str_to_size(&size)))
+                .unwrap_or(super::DEFAULT_SIZE),
+            dpi: self
+                .dpi
+                .and_then(|dpi| v.check("dpi", positive(dpi)))
+                .unwrap_or(super::DEFAULT_DPI),
+            spacing: {
+                let default = super::Spacing::default();
+                super::Spacing {
+                    line: v
+                        .check("lineSpace", not_negative(self.line_spacing))
+                        .unwrap_or(default.line),
+                    paragraph: self
+                        .paragraph_spacing
+                        .and_then(|space| v.check("paragraphSpace", not_negative(space)))
+                        .unwrap_or(default.paragraph),
+                    list_item: self
+                        .item_spacing
+                        .and_then(|space| v.check("itemSpace", not_negative(space)))
+                        .unwrap_or(default.list_item),
+                }
+            },
+            margin: {
+                let Rectangle { orig, size } = self.margin;
+                v.nested("margin".into(), |v| v.area(orig, size))
+            },
+            hyphenation: self
+                .hyphenate
+                .and_then(|lang| v.check("hyphenate", str_to_lang(&lang))),
+            colors: self
                 .colors
                 .iter()
-                // skip the # at the beginning
-                .map(|hex| hex_string_to_color(&hex[1..]))
+                .enumerate()
+                .filter_map(|(i, color)| v.check(&format!("colors[{}]", i), to_color(color)))
                 .collect(),
+        };
+
+        v.finish(style)
+    }
+}
+
+impl ListLevelJson {
+    /// fills in everything not given from the default,
+    /// colors are indices into a palette of the given length
+    fn into_level(
+        self,
+        default: super::ListLevel,
+        colors: usize,
+        dir: &Path,
+        v: &mut Validator,
+    ) -> super::ListLevel {
+        use super::Bullet;
+
+        let bullet = match (self.image, self.shape, self.bullet) {
+            (Some(path), _, _) => v
+                .check("image", existing_file(dir.join(path)))
+                .map(Bullet::Image)
+                .unwrap_or(default.bullet),
+            (None, Some(shape), _) => v
+                .check("shape", str_to_shape(&shape))
+                .unwrap_or(default.bullet),
+            (None, None, Some(glyph)) => Bullet::Glyph(glyph),
+            (None, None, None) => default.bullet,
+        };
+
+        super::ListLevel {
+            bullet,
+            color: self
+                .color
+                .and_then(|idx| v.check("color", in_palette(idx, colors)))
+                .or(default.color),
+            indent: self.indent.unwrap_or(default.indent),
+            spacing: self.spacing.unwrap_or(default.spacing),
         }
     }
 }
 
-impl From<SlideTemplate> for super::SlideTemplate {
-    fn from(json: SlideTemplate) -> Self {
-        Self {
-            decorations: json.decoration.into_iter().map(|d| d.into()).collect(),
-            content: json.template.into_iter().map(|t| t.into()).collect(),
+/// converts the templates of a file, every invalid value is returned
+pub fn into_templates(json: TemplateJson) -> Result<super::TemplateMap, Vec<Problem>> {
+    let mut v = Validator::default();
+    // the problems are reported in the same order every time
+    let mut templates: Vec<_> = json.into_iter().collect();
+    templates.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let map = templates
+        .into_iter()
+        .map(|(name, t)| {
+            let template = v.nested(name.clone(), |v| t.into_template(v));
+            (name, template)
+        })
+        .collect();
+
+    v.finish(map)
+}
+
+/// checks that every color index of the templates
+/// is inside of a palette with the given length
+pub fn check_palette(templates: &super::TemplateMap, colors: usize) -> Result<(), Vec<Problem>> {
+    let mut v = Validator::default();
+    let mut names: Vec<_> = templates.keys().collect();
+    names.sort();
+
+    for name in names {
+        let template = &templates[name];
+        v.nested(name.clone(), |v| {
+            for (i, d) in template.decorations.iter().enumerate() {
+                v.nested(format!("decoration[{}]", i), |v| {
+                    v.check("color", in_palette(d.color_idx, colors))
+                });
+            }
+            for (i, t) in template.content.iter().enumerate() {
+                if let Some(super::ColorRef::Index(idx)) = t.color {
+                    v.nested(format!("template[{}]", i), |v| {
+                        v.check("color", in_palette(idx, colors))
+                    });
+                }
+            }
+        });
+    }
+
+    v.finish(())
+}
+
+impl SlideTemplate {
+    fn into_template(self, v: &mut Validator) -> super::SlideTemplate {
+        super::SlideTemplate {
+            decorations: self
+                .decoration
+                .into_iter()
+                .enumerate()
+                .map(|(i, d)| v.nested(format!("decoration[{}]", i), |v| d.into_decoration(v)))
+                .collect(),
+            content: self
+                .template
+                .into_iter()
+                .enumerate()
+                .map(|(i, t)| v.nested(format!("template[{}]", i), |v| t.into_template(v)))
+                .collect(),
         }
     }
 }
 
-impl From<DecorationJson> for super::Decoration {
-    fn from(json: DecorationJson) -> Self {
-        let DecorationJson { orig, size, color } = json;
-        Self {
-            area: super::Rectangle { orig, size },
-            color_idx: color,
+impl DecorationJson {
+    fn into_decoration(self, v: &mut Validator) -> super::Decoration {
+        super::Decoration {
+            area: v.area(self.orig, self.size),
+            color_idx: self.color,
         }
     }
 }
 
-impl From<ContentTemplate> for super::ContentTemplate {
-    fn from(json: ContentTemplate) -> Self {
-        Self {
-            area: super::Rectangle {
-                orig: json.orig,
-                size: json.size,
+impl ContentTemplate {
+    fn into_template(self, v: &mut Validator) -> super::ContentTemplate {
+        super::ContentTemplate {
+            area: v.area(self.orig, self.size),
+            font: self.font,
+            font_size: v
+                .check("fontSize", positive(self.font_size))
+                .unwrap_or_default(),
+            variant: super::FontVariant {
+                weight: self
+                    .font_weight
+                    .and_then(|weight| v.check("fontWeight", to_weight(weight)))
+                    .unwrap_or_default(),
+                style: self
+                    .font_style
+                    .and_then(|style| v.check("fontStyle", str_to_style(&style)))
+                    .unwrap_or_default(),
+                stretch: self
+                    .font_stretch
+                    .and_then(|stretch| v.check("fontStretch", str_to_stretch(&stretch)))
+                    .unwrap_or_default(),
             },
-            font_size: json.font_size,
-            orientation: str_to_orientation(&json.orientation),
+            color: self
+                .color
+                .and_then(|color| v.check("color", to_color_ref(color))),
+            letter_spacing: self
+                .letter_spacing
+                .and_then(|spacing| v.check("letterSpacing", not_negative(spacing)))
+                .unwrap_or(0.0),
+            orientation: v
+                .check("orientation", str_to_orientation(&self.orientation))
+                .unwrap_or_default(),
+            spacing: super::SpacingOverride {
+                line: self
+                    .line_spacing
+                    .and_then(|space| v.check("lineSpace", not_negative(space))),
+                paragraph: self
+                    .paragraph_spacing
+                    .and_then(|space| v.check("paragraphSpace", not_negative(space))),
+                list_item: self
+                    .item_spacing
+                    .and_then(|space| v.check("itemSpace", not_negative(space))),
+            },
+            fit: self
+                .fit
+                .and_then(|fit| v.check("fit", str_to_fit(&fit)))
+                .unwrap_or_default(),
         }
     }
 }
 
-const ORIENT_ERR: &str = "orientation not in the right format";
+const POSITIVE_ERR: &str = "expected a number greater than 0";
+
+fn positive<T: PartialOrd + Default>(n: T) -> Result<T, &'static str> {
+    if n > T::default() {
+        Ok(n)
+    } else {
+        Err(POSITIVE_ERR)
+    }
+}
+
+const NEGATIVE_ERR: &str = "expected a number of at least 0";
+
+fn not_negative<T: PartialOrd + Default>(n: T) -> Result<T, &'static str> {
+    if n >= T::default() {
+        Ok(n)
+    } else {
+        Err(NEGATIVE_ERR)
+    }
+}
+
+const PALETTE_ERR: &str = "the style has no color with this index";
+
+fn in_palette(idx: usize, colors: usize) -> Result<usize, &'static str> {
+    if idx < colors {
+        Ok(idx)
+    } else {
+        Err(PALETTE_ERR)
+    }
+}
+
+const FILE_ERR: &str = "file not found";
+
+fn existing_file(path: PathBuf) -> Result<PathBuf, &'static str> {
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(FILE_ERR)
+    }
+}
+
+const SHAPE_ERR: &str = "bullet shape not known, expected square or circle";
+
+fn str_to_shape(s: &str) -> Result<super::Bullet, &'static str> {
+    match s.to_lowercase().as_str() {
+        "square" => Ok(super::Bullet::Square),
+        "circle" => Ok(super::Bullet::Circle),
+        _ => Err(SHAPE_ERR),
+    }
+}
+
+const FIT_ERR: &str = "fit mode not known, expected overflow, shrink, clip or continue";
+
+fn str_to_fit(s: &str) -> Result<super::Fit, &'static str> {
+    match s.to_lowercase().as_str() {
+        "overflow" => Ok(super::Fit::Overflow),
+        "shrink" => Ok(super::Fit::Shrink),
+        "clip" => Ok(super::Fit::Clip),
+        "continue" => Ok(super::Fit::Continue),
+        _ => Err(FIT_ERR),
+    }
+}
+
+const LANG_ERR: &str = "hyphenation language not known, expected a two letter code like en or de";
+
+fn str_to_lang(s: &str) -> Result<hypher::Lang, &'static str> {
+    let code: [u8; 2] = s
+        .to_lowercase()
+        .as_bytes()
+        .try_into()
+        .map_err(|_| LANG_ERR)?;
+    hypher::Lang::from_iso(code).ok_or(LANG_ERR)
+}
+
+const WEIGHT_ERR: &str = "font weight not known, expected a number from 1 to 1000 \
+                          or a name like normal or bold";
+
+/// converts the number or name of a weight
+fn to_weight(json: Value) -> Result<fontdb::Weight, &'static str> {
+    use fontdb::Weight;
+
+    Ok(match json {
+        Value::U64(n @ 1..=1000) => Weight(n as u16),
+        Value::String(name) => match name.to_lowercase().as_str() {
+            "thin" => Weight::THIN,
+            "extralight" | "extra-light" => Weight::EXTRA_LIGHT,
+            "light" => Weight::LIGHT,
+            "normal" | "regular" => Weight::NORMAL,
+            "medium" => Weight::MEDIUM,
+            "semibold" | "semi-bold" => Weight::SEMIBOLD,
+            "bold" => Weight::BOLD,
+            "extrabold" | "extra-bold" => Weight::EXTRA_BOLD,
+            "black" => Weight::BLACK,
+            _ => return Err(WEIGHT_ERR),
+        },
+        _ => return Err(WEIGHT_ERR),
+    })
+}
+
+const STYLE_ERR: &str = "font style not known, expected normal, italic or oblique";
+
+fn str_to_style(s: &str) -> Result<fontdb::Style, &'static str> {
+    match s.to_lowercase().as_str() {
+        "normal" => Ok(fontdb::Style::Normal),
+        "italic" => Ok(fontdb::Style::Italic),
+        "oblique" => Ok(fontdb::Style::Oblique),
+        _ => Err(STYLE_ERR),
+    }
+}
+
+const STRETCH_ERR: &str = "font stretch not known, expected a css keyword like condensed";
+
+fn str_to_stretch(s: &str) -> Result<fontdb::Stretch, &'static str> {
+    use fontdb::Stretch;
+
+    Ok(match s.to_lowercase().as_str() {
+        "ultra-condensed" => Stretch::UltraCondensed,
+        "extra-condensed" => Stretch::ExtraCondensed,
+        "condensed" => Stretch::Condensed,
+        "semi-condensed" => Stretch::SemiCondensed,
+        "normal" => Stretch::Normal,
+        "semi-expanded" => Stretch::SemiExpanded,
+        "expanded" => Stretch::Expanded,
+        "extra-expanded" => Stretch::ExtraExpanded,
+        "ultra-expanded" => Stretch::UltraExpanded,
+        _ => return Err(STRETCH_ERR),
+    })
+}
+
+const SIZE_ERR: &str = "slide size not known, expected 16:9, 4:3, 16:10, a4 \
+                        or the size with a unit like 1920x1080px, 297x210mm or 720x405pt";
+
+/// converts a preset or a size in the format
+/// of "<width>x<height><unit>" ex. "297x210mm"
+fn str_to_size(s: &str) -> Result<Size, &'static str> {
+    let lower: String = s.to_lowercase().split_whitespace().collect();
+
+    // the presets are as high as full hd
+    match lower.as_str() {
+        "16:9" => return Ok(Size::Px(1920, 1080)),
+        "4:3" => return Ok(Size::Px(1440, 1080)),
+        "16:10" => return Ok(Size::Px(1728, 1080)),
+        "a4" | "a4landscape" => return Ok(Size::Mm(297.0, 210.0)),
+        _ => {}
+    }
+
+    let (size, unit) = ["px", "mm", "pt"]
+        .iter()
+        .find_map(|unit| lower.strip_suffix(unit).map(|size| (size, *unit)))
+        .ok_or(SIZE_ERR)?;
+    let (x, y) = size.split_once('x').ok_or(SIZE_ERR)?;
+    let float = |n: &str| n.parse::<f64>().map_err(|_| SIZE_ERR).and_then(positive);
+    let int = |n: &str| n.parse::<usize>().map_err(|_| SIZE_ERR).and_then(positive);
+
+    Ok(match unit {
+        "px" => Size::Px(int(x)?, int(y)?),
+        "mm" => Size::Mm(float(x)?, float(y)?),
+        _ => Size::Pt(float(x)?, float(y)?),
+    })
+}
+
+const LENGTH_ERR: &str = "length not known, expected a fraction like 0.5, a percentage \
+                          like 50% or a length with a unit like 12mm, 40pt or 200px";
+
+/// converts a fraction, a percentage or
+/// a length with a unit, ex. "12mm"
+fn to_length(json: Value) -> Result<super::Length, &'static str> {
+    use super::Length;
+
+    let number = |n: &str| n.trim().parse::<f64>().map_err(|_| LENGTH_ERR);
+    Ok(match json {
+        Value::F64(n) => Length::Relative(n),
+        Value::U64(n) => Length::Relative(n as f64),
+        Value::I64(n) => Length::Relative(n as f64),
+        Value::String(s) => {
+            let s = s.trim().to_lowercase();
+            if let Some(n) = s.strip_suffix('%') {
+                Length::Relative(number(n)? / 100.0)
+            } else if let Some(n) = s.strip_suffix("mm") {
+                Length::Mm(number(n)?)
+            } else if let Some(n) = s.strip_suffix("pt") {
+                Length::Pt(number(n)?)
+            } else if let Some(n) = s.strip_suffix("px") {
+                Length::Px(number(n)?)
+            } else {
+                Length::Relative(number(&s)?)
+            }
+        }
+        _ => return Err(LENGTH_ERR),
+    })
+}
+
+const ORIENT_ERR: &str = "orientation not known, expected the vertical (top, middle, bottom) \
+                          and horizontal (left, middle, right, justify) one, ex. top left";
 
 /// converts a string in the format of "<vert> <hor>"
 /// ex. "top left" into an orientation
-fn str_to_orientation(s: &str) -> super::Orientation {
+fn str_to_orientation(s: &str) -> Result<super::Orientation, &'static str> {
     use super::{HorOrientation, VertOrientation};
     let lower = s.to_lowercase();
     let mut words = lower.split_whitespace();
 
-    let vert = match words.next().expect(ORIENT_ERR) {
-        "bottom" => VertOrientation::Bottom,
-        "middle" => VertOrientation::Middle,
-        "top" => VertOrientation::Top,
-        _ => panic!("{}", ORIENT_ERR),
+    let vert = match words.next() {
+        Some("bottom") => VertOrientation::Bottom,
+        Some("middle") => VertOrientation::Middle,
+        Some("top") => VertOrientation::Top,
+        _ => return Err(ORIENT_ERR),
     };
 
-    let hort = match words.next().expect(ORIENT_ERR) {
-        "left" => HorOrientation::Left,
-        "middle" => HorOrientation::Middle,
-        "right" => HorOrientation::Right,
-        _ => panic!("{}", ORIENT_ERR),
+    let hort = match words.next() {
+        Some("left") => HorOrientation::Left,
+        Some("middle") => HorOrientation::Middle,
+        Some("right") => HorOrientation::Right,
+        Some("justify") => HorOrientation::Justify,
+        _ => return Err(ORIENT_ERR),
     };
 
     // nothing else behind it
-    assert_eq!(words.next(), None, "{}", ORIENT_ERR);
+    if words.next().is_some() {
+        return Err(ORIENT_ERR);
+    }
 
-    super::Orientation {
+    Ok(super::Orientation {
         vertical: vert,
         horizontal: hort,
+    })
+}
+
+const COLOR_REF_ERR: &str = "text color not known, expected the index of a color \
+                             of the style or a color like %FF0000";
+
+/// converts the index or the hex string of a color
+fn to_color_ref(json: Value) -> Result<super::ColorRef, &'static str> {
+    match json {
+        Value::U64(idx) => Ok(super::ColorRef::Index(idx as usize)),
+        Value::String(hex) => to_color(&hex).map(super::ColorRef::Literal),
+        _ => Err(COLOR_REF_ERR),
     }
 }
 
-fn hex_string_to_color(hex: &str) -> super::Color {
-    let to_color = |c| c as f64 / 256.0;
-    let bytes = u32::from_str_radix(hex, 16)
-        .expect("wrong color format expected RRGGBB in hex")
-        .to_le_bytes();
+const COLOR_ERR: &str = "color not known, expected %RGB, %RRGGBB or %RRGGBBAA in hex, \
+    rgb(), rgba(), hsl(), hsla() or a css name, ex. %FF0000 or rgb(255, 0, 0)";
+
+/// converts a css color like "rgb(255, 0, 0)" or "red", hex colors start
+/// with % instead of #, because # begins a comment in hjson
+fn to_color(s: &str) -> Result<super::Color, &'static str> {
+    let s = s.trim();
+    let color = match s.strip_prefix('%') {
+        Some(hex) => csscolorparser::parse(&format!("#{}", hex)),
+        None => csscolorparser::parse(s),
+    }
+    .map_err(|_| COLOR_ERR)?;
+
+    Ok(super::Color {
+        r: color.r,
+        g: color.g,
+        b: color.b,
+        a: color.a,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    fn problem_keys(hjson: &str) -> Vec<String> {
+        let json: super::TemplateJson = serde_hjson::from_str(hjson).unwrap();
+        super::into_templates(json)
+            .unwrap_err()
+            .into_iter()
+            .map(|p| p.key)
+            .collect()
+    }
+
+    #[test]
+    fn problems_have_key_paths() {
+        let keys = problem_keys(
+            r#"{
+                Title: {
+                    decoration: [{orig: {x: 0, y: "5 apples"}, size: {x: 1, y: 1}, color: 0}]
+                    template: [
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20}
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "up", fontSize: 20}
+                    ]
+                }
+            }"#,
+        );
+
+        assert_eq!(
+            keys,
+            vec![
+                "Title.decoration[0].orig.y",
+                "Title.template[1].orientation"
+            ]
+        );
+    }
+
+    #[test]
+    fn every_template_is_checked() {
+        let keys = problem_keys(
+            r#"{
+                B: {
+                    decoration: []
+                    template: [{orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20, fit: "squeeze"}]
+                }
+                A: {
+                    decoration: []
+                    template: [{orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20, fontWeight: 0}]
+                }
+            }"#,
+        );
+
+        assert_eq!(keys, vec!["A.template[0].fontWeight", "B.template[0].fit"]);
+    }
+
+    #[test]
+    fn values_out_of_range_are_problems() {
+        let keys = problem_keys(
+            r#"{
+                Title: {
+                    decoration: []
+                    template: [
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 0}
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20, letterSpacing: -0.1, itemSpace: -1}
+                    ]
+                }
+            }"#,
+        );
+        assert_eq!(
+            keys,
+            vec![
+                "Title.template[0].fontSize",
+                "Title.template[1].letterSpacing",
+                "Title.template[1].itemSpace"
+            ]
+        );
+
+        let style: super::StyleJson = serde_hjson::from_str(
+            r#"{
+                colors: []
+                font: "DejaVu Sans"
+                size: "-5x3mm"
+                dpi: 0
+                margin: {orig: {x: 0, y: 0}, size: {x: 1, y: 1}}
+                lineSpace: -1.0
+                paragraphSpace: -0.5
+            }"#,
+        )
+        .unwrap();
+        let keys: Vec<_> = style
+            .into_style(std::path::Path::new(""))
+            .unwrap_err()
+            .into_iter()
+            .map(|p| p.key)
+            .collect();
+        assert_eq!(keys, vec!["size", "dpi", "lineSpace", "paragraphSpace"]);
+    }
+
+    #[test]
+    fn colors_need_to_be_in_the_palette() {
+        let json: super::TemplateJson = serde_hjson::from_str(
+            r#"{
+                Head_Cont: {
+                    decoration: [{orig: {x: 0, y: 0}, size: {x: 1, y: 1}, color: 6}]
+                    template: [
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20, color: 9}
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20, color: 5}
+                        {orig: {x: 0, y: 0}, size: {x: 1, y: 1}, orientation: "top left", fontSize: 20, color: "%FF0000"}
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+        let templates = super::into_templates(json).unwrap();
+        let keys: Vec<_> = super::check_palette(&templates, 6)
+            .unwrap_err()
+            .into_iter()
+            .map(|p| p.key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "Head_Cont.decoration[0].color",
+                "Head_Cont.template[0].color"
+            ]
+        );
+        assert!(super::check_palette(&templates, 10).is_ok());
+
+        let style: super::StyleJson = serde_hjson::from_str(
+            r#"{
+                colors: ["%000", "%FFF"]
+                font: "DejaVu Sans"
+                margin: {orig: {x: 0, y: 0}, size: {x: 1, y: 1}}
+                lineSpace: 1.0
+                list: [{color: 1}, {color: 2}, {image: "no_bullet.png"}]
+            }"#,
+        )
+        .unwrap();
+        let keys: Vec<_> = style
+            .into_style(std::path::Path::new(""))
+            .unwrap_err()
+            .into_iter()
+            .map(|p| p.key)
+            .collect();
+        assert_eq!(keys, vec!["list[1].color", "list[2].image"]);
+    }
+
+    #[test]
+    fn sizes() {
+        use super::{str_to_size, Size};
+
+        assert_eq!(str_to_size("16:9"), Ok(Size::Px(1920, 1080)));
+        assert_eq!(str_to_size("A4"), Ok(Size::Mm(297.0, 210.0)));
+        assert_eq!(str_to_size("297x210mm"), Ok(Size::Mm(297.0, 210.0)));
+        assert_eq!(str_to_size("1920x1080px"), Ok(Size::Px(1920, 1080)));
+        assert!(str_to_size("12x").is_err());
+        assert!(str_to_size("axbpt").is_err());
+        assert!(str_to_size("0x0mm").is_err());
+    }
+
+    #[test]
+    fn colors() {
+        let rgba = |s| super::to_color(s).map(|c| (c.r, c.g, c.b, c.a));
 
-    super::Color {
-        b: to_color(bytes[0]),
-        g: to_color(bytes[1]),
-        r: to_color(bytes[2]),
+        assert_eq!(rgba("%FFFFFF"), Ok((1.0, 1.0, 1.0, 1.0)));
+        assert_eq!(rgba("%F00"), Ok((1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(rgba("%0000FF00"), Ok((0.0, 0.0, 1.0, 0.0)));
+        assert_eq!(rgba("rgb(0, 255, 0)"), Ok((0.0, 1.0, 0.0, 1.0)));
+        assert_eq!(rgba("hsla(0, 100%, 50%, 0.5)"), Ok((1.0, 0.0, 0.0, 0.5)));
+        assert_eq!(rgba("Black"), Ok((0.0, 0.0, 0.0, 1.0)));
+        assert!(rgba("%FF00F").is_err());
+        assert!(rgba("blurple").is_err());
     }
 }
@@ -10,6 +10,17 @@ impl<'a> Default for super::Config<'a> {
     }
 }
 
+pub fn default_list_levels() -> Vec<ListLevel> {
+    let level = |bullet: &str, indent| ListLevel {
+        bullet: Bullet::Glyph(String::from(bullet)),
+        color: None,
+        indent,
+        spacing: 1.5,
+    };
+
+    vec![level("•", 0.0), level("–", 1.5), level("-", 1.5)]
+}
+
 pub fn default_slide_templates() -> TemplateMap {
     let header_orientation = Orientation {
         vertical: VertOrientation::Bottom,
@@ -22,16 +33,28 @@ pub fn default_slide_templates() -> TemplateMap {
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.0},
-                        size: Point{x: 1.0,y: 0.8} },
+                        size: Point{x: 1.0,y: 0.8} }.map(Length::Relative),
                     font_size: 36.0,
                     orientation: header_orientation.clone(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.8},
-                        size: Point{x: 1.0,y: 0.2} },
+                        size: Point{x: 1.0,y: 0.2} }.map(Length::Relative),
                     font_size: 18.0,
                     orientation: Orientation::default(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
             ],
         },
@@ -43,17 +66,29 @@ pub fn default_slide_templates() -> TemplateMap {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.0},
                         size: Point{x: 1.0,y: 0.3},
-                    },
+                    }.map(Length::Relative),
                     font_size: 24.0,
                     orientation: header_orientation.clone(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.3},
                         size: Point{x: 1.0,y: 0.7},
-                    },
+                    }.map(Length::Relative),
                     font_size: 18.0,
                     orientation: Orientation::default(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
             ],
         },
@@ -65,33 +100,57 @@ pub fn default_slide_templates() -> TemplateMap {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.0},
                         size: Point{x: 0.5,y: 0.3},
-                    },
+                    }.map(Length::Relative),
                     font_size: 24.0,
                     orientation: header_orientation.clone(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.3},
                         size: Point{x: 0.5,y: 0.7},
-                    },
+                    }.map(Length::Relative),
                     font_size: 18.0,
                     orientation: Orientation::default(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.5,y: 0.0},
                         size: Point{x: 0.5,y: 0.3},
-                    },
+                    }.map(Length::Relative),
                     font_size: 24.0,
                     orientation: header_orientation,
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.5,y: 0.3},
                         size: Point{x: 0.5,y: 0.7},
-                    },
+                    }.map(Length::Relative),
                     font_size: 18.0,
                     orientation: Orientation::default(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
             ],
         },
@@ -102,17 +161,29 @@ pub fn default_slide_templates() -> TemplateMap {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.0},
                         size: Point{x: 1.0,y: 0.5},
-                    },
+                    }.map(Length::Relative),
                     font_size: 20.0,
                     orientation: Orientation::default(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
                 ContentTemplate {
                     area: Rectangle {
                         orig: Point{x: 0.0,y: 0.5},
                         size: Point{x: 1.0,y: 0.5},
-                    },
+                    }.map(Length::Relative),
                     font_size: 20.0,
                     orientation: Orientation::default(),
+                    font: None,
+                    variant: FontVariant::default(),
+                    color: None,
+                    letter_spacing: 0.0,
+                    spacing: SpacingOverride::default(),
+                    fit: Fit::default(),
                 },
             ],
         },
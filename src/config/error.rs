@@ -0,0 +1,47 @@
+use std::io;
+use std::path::PathBuf;
+
+/// a value of a style or template which can't be used
+#[derive(Debug)]
+pub struct Problem {
+    /// where the value is inside the file, ex. "Head_Cont.template[1].orientation"
+    pub key: String,
+    pub message: &'static str,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    File(PathBuf, io::Error),
+    /// the file isn't hjson or has values of the wrong type
+    Format(PathBuf, serde_hjson::Error),
+    /// every value of the file which can't be used
+    Invalid(PathBuf, Vec<Problem>),
+}
+
+impl std::error::Error for ConfigError {}
+
+use std::fmt;
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConfigError::*;
+        match self {
+            File(path, e) => write!(f, "{}: {}", path.display(), e),
+            Format(path, e) => write!(f, "invalid format of {} due to: {}", path.display(), e),
+            Invalid(path, problems) => {
+                for (i, problem) in problems.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(
+                        f,
+                        "{}: {}: {}",
+                        path.display(),
+                        problem.key,
+                        problem.message
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
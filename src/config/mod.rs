@@ -1,4 +1,5 @@
 use crate::drawing::error::DrawError;
+use crate::util::pdf::Size;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -7,23 +8,99 @@ pub type TemplateMap = HashMap<String, SlideTemplate>;
 
 mod de_se;
 mod default;
+mod error;
 mod primitives;
 
+pub use error::{ConfigError, Problem};
 pub use primitives::*;
 
 use self::default::default_slide_templates;
 
 #[derive(Debug)]
 pub struct Decoration {
-    pub area: Rectangle<f64>,
+    pub area: Rectangle<Length>,
     pub color_idx: usize,
 }
 
 #[derive(Debug)]
 pub struct ContentTemplate {
-    pub area: Rectangle<f64>,
+    pub area: Rectangle<Length>,
+    /// overrides the font family of the style
+    pub font: Option<String>,
     pub font_size: f32,
+    /// the variant of the font, bold or italic text is drawn bolder or italic
+    pub variant: FontVariant,
+    /// the color of text without a color of its own
+    pub color: Option<ColorRef>,
+    /// extra space after every glyph, in multiples of the font size
+    pub letter_spacing: f64,
     pub orientation: Orientation,
+    /// overrides the spacing of the style
+    pub spacing: SpacingOverride,
+    /// what happens with content higher than the area
+    pub fit: Fit,
+}
+
+/// how content is treated that doesn't fit into its area
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Fit {
+    /// it's drawn beyond the area
+    #[default]
+    Overflow,
+    /// the font size is decreased until it fits
+    Shrink,
+    /// everything outside of the area is cut off
+    Clip,
+    /// the rest is drawn on a follow-up slide
+    Continue,
+}
+
+/// a color of the palette of the style or one given directly
+#[derive(Debug, Clone, Copy)]
+pub enum ColorRef {
+    Index(usize),
+    Literal(Color),
+}
+
+impl ContentTemplate {
+    /// the spacing of the style with the overrides of this template
+    pub fn spacing(&self, style: &Spacing) -> Spacing {
+        let o = &self.spacing;
+        Spacing {
+            line: o.line.unwrap_or(style.line),
+            paragraph: o.paragraph.unwrap_or(style.paragraph),
+            list_item: o.list_item.unwrap_or(style.list_item),
+        }
+    }
+}
+
+/// the space between lines, paragraphs and list items
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spacing {
+    /// factor for the height of a line
+    pub line: f64,
+    /// space between paragraphs, in multiples of the font size
+    pub paragraph: f64,
+    /// space between list items, in multiples of the font size
+    pub list_item: f64,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Self {
+            line: 1.0,
+            paragraph: 0.5,
+            list_item: 0.25,
+        }
+    }
+}
+
+/// the spacing a template changes, none keeps the one of the style
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpacingOverride {
+    pub line: Option<f64>,
+    pub paragraph: Option<f64>,
+    pub list_item: Option<f64>,
 }
 
 #[derive(Debug)]
@@ -35,12 +112,78 @@ pub struct SlideTemplate {
     pub content: Vec<ContentTemplate>,
 }
 
+/// what is drawn in front of an unordered list item
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bullet {
+    /// some text drawn with the font of the item
+    Glyph(String),
+    Square,
+    Circle,
+    Image(PathBuf),
+}
+
+/// how the items of one nesting level of a list are drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListLevel {
+    pub bullet: Bullet,
+    /// index of the color of the bullet or number,
+    /// none uses the color of the text
+    pub color: Option<usize>,
+    /// how far the bullet is moved to the right of the
+    /// bullet of the level above, in multiples of the font size
+    pub indent: f64,
+    /// distance between the start of the bullet and the text,
+    /// in multiples of the font size
+    pub spacing: f64,
+}
+
 #[derive(Debug)]
 pub struct PresentStyle {
     pub colors: Vec<Color>,
     pub font: String,
-    pub margin: Rectangle<f64>,
-    line_spacing: f64,
+    /// font used for `code` spans
+    pub code_font: String,
+    /// fonts for the chars the other fonts can't render,
+    /// the first one which can render a char is used
+    pub fallback_fonts: Vec<String>,
+    /// font files and directories with fonts,
+    /// which are preferred to the fonts of the system
+    pub fonts: Vec<PathBuf>,
+    /// the size of the slides, only the one
+    /// of the first style is used
+    pub size: Size,
+    /// resolution used to convert pixels
+    pub dpi: u16,
+    /// the drawing area of the slides, relative to the whole slide
+    pub margin: Rectangle<Length>,
+    pub spacing: Spacing,
+    /// the look of every nesting level of lists,
+    /// deeper levels use the last one
+    pub list: Vec<ListLevel>,
+    /// the language of the text, if words should be hyphenated.
+    /// There's one per style, a Style slide can change it
+    pub hyphenation: Option<hypher::Lang>,
+}
+
+impl PresentStyle {
+    /// reads the style, the paths inside
+    /// it are relative to the style itself
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let json: de_se::StyleJson = serde_hjson::from_reader(get_reader(path)?)
+            .map_err(|e| ConfigError::Format(path.into(), e))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        json.into_style(dir)
+            .map_err(|problems| ConfigError::Invalid(path.into(), problems))
+    }
+
+    /// the look of the list items at the nesting level
+    pub fn list_level(&self, level: usize) -> &ListLevel {
+        // PANICS: there is always at least one level
+        self.list
+            .get(level)
+            .unwrap_or_else(|| self.list.last().unwrap())
+    }
 }
 
 impl Default for PresentStyle {
@@ -54,13 +197,25 @@ impl Default for PresentStyle {
             margin: Rectangle {
                 orig: Point { x: 0.05, y: 0.05 },
                 size: Point { x: 0.9, y: 0.9 },
-            },
+            }
+            .map(Length::Relative),
             font: String::from("Noto Sans"),
-            line_spacing: 1.0,
+            code_font: String::from("monospace"),
+            fallback_fonts: vec![],
+            fonts: vec![],
+            size: DEFAULT_SIZE,
+            dpi: DEFAULT_DPI,
+            spacing: Spacing::default(),
+            list: default::default_list_levels(),
+            hyphenation: None,
         }
     }
 }
 
+/// full hd, like most screens and projectors
+const DEFAULT_SIZE: Size = Size::Px(1920, 1080);
+const DEFAULT_DPI: u16 = 300;
+
 #[derive(Default)]
 pub struct ConfigBuilder {
     pub style: Option<PathBuf>,
@@ -83,53 +238,50 @@ impl ConfigBuilder {
             .style
             .as_ref()
             .ok_or("no style given".into())
-            .map(|s| {
-                let r = get_reader(s).map_err(|_| Cow::Borrowed(""))?;
-                let json: de_se::StyleJson = serde_hjson::from_reader(r).map_err(|e| {
-                    Cow::Owned(format!(
-                        "invalid style format: {}, due to: {}\n",
-                        s.to_string_lossy(),
-                        e
-                    ))
-                })?;
-                Ok(PresentStyle::from(json))
-            })
+            .map(|s| PresentStyle::read(s).map_err(|e| Cow::Owned(e.to_string())))
             .flatten();
 
         if let Err(e) = &style {
-            eprintln!("{}\tusing default style instead", e);
+            eprintln!("{}\n\tusing default style instead", e);
         }
 
         style.unwrap_or_default()
     }
 
+    /// the templates of the files, their color indices
+    /// need to be inside of a palette with the given length
     fn parse_templates<'a, I: Iterator<Item = &'a PathBuf> + 'a>(
         paths: I,
-    ) -> impl Iterator<Item = Result<impl Iterator<Item = (String, SlideTemplate)>, String>> + 'a
-    {
+        colors: usize,
+    ) -> impl Iterator<Item = Result<TemplateMap, String>> + 'a {
         paths
-            .filter_map(|p| Some((p, get_reader(p).ok()?))) // only valid files are processed
-            .map(|(p, r)| {
-                let json: de_se::TemplateJson = serde_hjson::from_reader(r).map_err(|e| {
-                    format!(
-                        "invalid template at: {} due to:\n{}",
-                        p.to_string_lossy(),
-                        e
-                    )
-                })?;
-
-                Ok(json.into_iter().map(|(k, t)| (k, SlideTemplate::from(t))))
+            // only valid files are processed
+            .filter_map(|p| match get_reader(p) {
+                Ok(r) => Some((p, r)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            })
+            .map(move |(p, r)| {
+                let json: de_se::TemplateJson = serde_hjson::from_reader(r)
+                    .map_err(|e| ConfigError::Format(p.clone(), e).to_string())?;
+
+                de_se::into_templates(json)
+                    .and_then(|t| de_se::check_palette(&t, colors).map(|_| t))
+                    .map_err(|problems| ConfigError::Invalid(p.clone(), problems).to_string())
             })
     }
 
-    fn get_templates(&self) -> TemplateMap {
+    fn get_templates(&self, style: &PresentStyle) -> TemplateMap {
         let map: Result<_, Cow<str>> = try {
             let paths = self
                 .templates
                 .as_ref()
                 .ok_or(Cow::Borrowed("no templates given"))?;
 
-            Self::parse_templates(paths.iter()).try_fold(TemplateMap::new(), |mut map, t| {
+            let mut templates = Self::parse_templates(paths.iter(), style.colors.len());
+            templates.try_fold(TemplateMap::new(), |mut map, t| {
                 map.extend(t?);
                 Ok::<_, String>(map)
             })?
@@ -151,9 +303,10 @@ impl ConfigBuilder {
     }
 
     pub fn build(self, doc_name: &'_ str) -> Config<'_> {
+        let style = self.get_style();
         Config {
-            style: self.get_style(),
-            slide_templates: self.get_templates(),
+            slide_templates: self.get_templates(&style),
+            style,
             doc_name,
         }
     }
@@ -166,16 +319,11 @@ pub struct Config<'a> {
     pub doc_name: &'a str,
 }
 
-fn get_reader<P: AsRef<Path>>(path: P) -> io::Result<io::BufReader<fs::File>> {
-    let file = fs::File::open(path.as_ref());
-
-    match file {
-        Ok(f) => Ok(io::BufReader::new(f)),
-        Err(e) => {
-            eprintln!("{}: {}", path.as_ref().to_string_lossy(), e);
-            Err(e)
-        }
-    }
+fn get_reader<P: AsRef<Path>>(path: P) -> Result<io::BufReader<fs::File>, ConfigError> {
+    let path = path.as_ref();
+    fs::File::open(path)
+        .map(io::BufReader::new)
+        .map_err(|e| ConfigError::File(path.into(), e))
 }
 
 impl<'a> Config<'a> {
@@ -183,10 +331,16 @@ impl<'a> Config<'a> {
         ConfigBuilder::default()
     }
 
-    /// change the style to the one specified inside the path
-    pub fn change_style<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        let json: de_se::StyleJson = serde_hjson::from_reader(get_reader(path)?).unwrap();
-        self.style = json.into();
+    /// change the style to the one specified inside the path, the style
+    /// stays the same if the new one can't be used, ex. if its palette
+    /// lacks colors of the templates
+    pub fn change_style<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let style = PresentStyle::read(path)?;
+        de_se::check_palette(&self.slide_templates, style.colors.len())
+            .map_err(|problems| ConfigError::Invalid(path.into(), problems))?;
+
+        self.style = style;
         Ok(())
     }
 
@@ -197,4 +351,12 @@ impl<'a> Config<'a> {
             .ok_or(DrawError::NoColor(idx))
             .map(|c| *c)
     }
+
+    /// the color the reference stands for
+    pub fn resolve_color(&self, color: ColorRef) -> Result<Color, DrawError> {
+        match color {
+            ColorRef::Index(idx) => self.get_color(idx),
+            ColorRef::Literal(c) => Ok(c),
+        }
+    }
 }
@@ -9,11 +9,18 @@ pub struct Color {
     pub r: f64,
     pub g: f64,
     pub b: f64,
+    /// the opacity, 0.0 is invisible
+    pub a: f64,
 }
 
 impl Color {
+    /// an opaque color
     pub fn new(r: f64, g: f64, b: f64) -> Self {
-        Color { r, g, b }
+        Color { r, g, b, a: 1.0 }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.a >= 1.0
     }
 }
 
@@ -93,13 +100,56 @@ pub struct Rectangle<T> {
     pub size: Point<T>,
 }
 
-impl<T> Rectangle<T>
-where
-    T: PartialOrd + ops::Add<Output = T> + Copy,
-{
-    /// checks whether the other rectangle is inside this one
-    pub fn is_inside_inclusive(&self, other: Point<T>) -> bool {
-        self.orig <= other && self.orig + self.size >= other
+impl<T> Rectangle<T> {
+    pub fn map<F, U>(self, mut f: F) -> Rectangle<U>
+    where
+        F: FnMut(T) -> U,
+    {
+        Rectangle {
+            orig: self.orig.map(&mut f),
+            size: self.size.map(f),
+        }
+    }
+}
+
+/// a length of an area, relative to the area it's placed in or absolute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    /// a fraction of the surrounding area, 1.0 is all of it
+    Relative(f64),
+    Mm(f64),
+    Pt(f64),
+    /// converted with the dpi of the style
+    Px(f64),
+}
+
+/// the variant of a font family, which face of the
+/// family is used is decided by the font database
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontVariant {
+    pub weight: fontdb::Weight,
+    pub style: fontdb::Style,
+    pub stretch: fontdb::Stretch,
+}
+
+impl FontVariant {
+    /// the variant with at least a bold weight
+    pub fn bold(self) -> Self {
+        Self {
+            weight: self.weight.max(fontdb::Weight::BOLD),
+            ..self
+        }
+    }
+
+    /// the italic variant, oblique faces stay oblique
+    pub fn italic(self) -> Self {
+        Self {
+            style: match self.style {
+                fontdb::Style::Normal => fontdb::Style::Italic,
+                s => s,
+            },
+            ..self
+        }
     }
 }
 
@@ -115,6 +165,9 @@ pub enum HorOrientation {
     Left,
     Middle,
     Right,
+    /// stretches the space between the words, so that
+    /// every line except the last of a paragraph fills the width
+    Justify,
 }
 
 #[derive(Debug, Clone, PartialEq)]
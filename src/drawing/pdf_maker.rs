@@ -1,12 +1,26 @@
 use super::{DResult, DrawError, Drawer};
-use crate::config::{self, Config, ContentTemplate, Decoration};
-use crate::parser::{Content, Slide};
+use crate::config::{self, Config, ContentTemplate, Decoration, Fit};
+use crate::parser::{Content, ListItem, ListKind, Run, Slide};
 use crate::util::pdf;
+use printpdf::Pt;
 use std::io::Write;
 
-const DPI: u16 = 300;
-// TODO: maybe look for the screen size
-const SIZE: pdf::Size = pdf::Size::Px(1920, 1080);
+/// the smallest font size text is shrunk to
+const MIN_FONT_SIZE: f64 = 4.0;
+/// shrinking stops when the font size is known this exact
+const FONT_SIZE_PRECISION: f64 = 0.1;
+const TOP_LEFT: config::Orientation = config::Orientation {
+    vertical: config::VertOrientation::Top,
+    horizontal: config::HorOrientation::Left,
+};
+const TOP_RIGHT: config::Orientation = config::Orientation {
+    vertical: config::VertOrientation::Top,
+    horizontal: config::HorOrientation::Right,
+};
+const TOP_JUSTIFY: config::Orientation = config::Orientation {
+    vertical: config::VertOrientation::Top,
+    horizontal: config::HorOrientation::Justify,
+};
 
 pub struct PdfMaker {
     doc: pdf::Document,
@@ -20,11 +34,22 @@ impl Drawer for PdfMaker {
             .get(&slide.kind)
             .ok_or_else(|| DrawError::KindNotFound(slide.kind.clone()))?;
 
-        // create the new pdf page for the slide
-        let mut page = self.doc.new_page("");
+        // the style could have been changed with new fonts
+        self.doc.add_fonts(&config.style.fonts);
 
-        Self::draw_decorations(&mut page, &kind.decorations, config)?;
-        Self::draw_content(&mut page, &kind.content, slide, &config.style.font)
+        // content that doesn't fit is continued on the next page
+        let mut next = Some(slide);
+        let mut continued = false;
+        while let Some(slide) = next {
+            // create the new pdf page for the slide
+            let mut page = self.doc.new_page("");
+
+            Self::draw_decorations(&mut page, &kind.decorations, config)?;
+            next = Self::draw_content(&mut page, &kind.content, slide, continued, config)?;
+            continued = true;
+        }
+
+        Ok(())
     }
 
     /// writes the document to the file system
@@ -37,8 +62,15 @@ impl PdfMaker {
     /// creates a pdf maker with information from the
     /// config
     pub fn with_config(config: &Config) -> DResult<Self> {
-        let doc = pdf::Document::new(config.doc_name, SIZE, config.style.margin.clone(), DPI)?;
-        let drawer = Self { doc };
+        let doc = pdf::Document::new(
+            config.doc_name,
+            config.style.size,
+            config.style.margin.clone(),
+            config.style.dpi,
+            config.style.fallback_fonts.clone(),
+        )?;
+        let mut drawer = Self { doc };
+        drawer.doc.add_fonts(&config.style.fonts);
 
         Ok(drawer)
     }
@@ -53,79 +85,484 @@ impl PdfMaker {
             let area = page.doc.scale_pdf_rect(d.area.clone());
             let color = config.get_color(d.color_idx)?;
 
-            page.draw_rect(&area, Some(color.into()), None)
+            page.draw_rect(&area, Some(color), None)
         }
 
         Ok(())
     }
 
-    /// draws the content of a slide to the pdf page
+    /// converts the styled runs of the parser into runs with the fonts
+    /// and colors of the style, unstyled text looks like the args say
+    fn text_runs<'r>(
+        runs: &'r [Run],
+        args: &pdf::TextArgs<'r>,
+        config: &'r Config,
+    ) -> DResult<Vec<pdf::TextRun<'r>>> {
+        let variant = args.variant;
+        runs.iter()
+            .map(|r| {
+                Ok(pdf::TextRun {
+                    text: &r.text,
+                    font: if r.style.code {
+                        &config.style.code_font
+                    } else {
+                        args.font
+                    },
+                    variant: match (r.style.bold, r.style.italic) {
+                        (false, false) => variant,
+                        (true, false) => variant.bold(),
+                        (false, true) => variant.italic(),
+                        (true, true) => variant.bold().italic(),
+                    },
+                    color: match r.style.color {
+                        Some(i) => Some(config.get_color(i)?),
+                        None => args.color,
+                    },
+                    letter_spacing: args.letter_spacing,
+                })
+            })
+            .collect()
+    }
+
+    /// draws the content of a slide to the pdf page,
+    /// returns the follow-up slide if some content needs to be continued.
+    /// The slide is a follow-up slide itself if continued is true
     fn draw_content(
         page: &mut pdf::Page,
         contents: &[ContentTemplate],
         slide: Slide,
-        font: &str,
-    ) -> DResult<()> {
-        for (template, content) in contents.iter().zip(slide.contents.into_iter()) {
+        continued: bool,
+        config: &Config,
+    ) -> DResult<Option<Slide>> {
+        // the first area holds the title, which is repeated on follow-up slides
+        let title = match slide.contents.first() {
+            Some(Content::Text(runs)) if continued => Some(runs.clone()),
+            Some(Content::Text(runs)) => {
+                let mut title = runs.clone();
+                title.push(Run {
+                    text: String::from(" (cont.)"),
+                    style: Default::default(),
+                });
+                Some(title)
+            }
+            _ => None,
+        };
+        let mut rest: Vec<Option<Content>> = vec![];
+
+        for (i, (template, mut content)) in contents.iter().zip(slide.contents).enumerate() {
+            if let Some(lang) = config.style.hyphenation {
+                hyphenate(&mut content, lang);
+            }
+
             let area = page.doc.scale_pdf_rect(template.area.clone());
-            let args = pdf::TextArgs {
+            let available = area.size().y;
+            let spacing = template.spacing(&config.style.spacing);
+            let mut args = pdf::TextArgs {
                 area,
                 font_size: template.font_size as f64,
-                font,
+                font: template.font.as_deref().unwrap_or(&config.style.font),
+                variant: template.variant,
+                color: template
+                    .color
+                    .map(|c| config.resolve_color(c))
+                    .transpose()?,
+                letter_spacing: template.letter_spacing,
                 orientation: &template.orientation,
+                line_spacing: spacing.line,
+                paragraph_spacing: spacing.paragraph,
             };
 
-            match content {
-                Content::Text(s) => {
-                    page.draw_text(&args, &s)?;
+            match template.fit {
+                Fit::Shrink => Self::shrink(page, &content, &mut args, spacing.list_item, config)?,
+                Fit::Clip => page.start_clip(&args.area),
+                Fit::Continue => {
+                    let (now, later) =
+                        Self::split_content(page, content, &args, spacing.list_item, config)?;
+                    content = now;
+                    rest.resize_with(i + 1, || None);
+                    rest[i] = later;
+                }
+                Fit::Overflow => (),
+            }
+
+            let height = match content {
+                Content::Text(runs) => {
+                    page.draw_text(&args, &Self::text_runs(&runs, &args, config)?)?
                 }
                 Content::Config(_) => panic!("Config calls should be handled before drawing"),
                 Content::Image(_, p) => {
                     // TODO: add description
                     page.draw_image(p, &args.area)?;
+                    available
                 }
-                Content::List(i) => Self::list(page, i, args)?,
+                Content::List(items) => {
+                    let list = Self::measure_list(page, &items, &args, spacing.list_item, config)?;
+                    Self::draw_list(page, list, &args, spacing.list_item)?
+                }
+            };
+
+            if template.fit == Fit::Clip {
+                page.end_clip();
+            } else if height > available {
+                eprintln!(
+                    "warning: the content of area {} on slide `{}` (line {}) doesn't fit, \
+                     it's {:.1}pt higher than the area",
+                    i + 1,
+                    slide.kind,
+                    slide.line,
+                    (height - available).0
+                );
             }
         }
 
-        Ok(())
+        if rest.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+
+        // everything which isn't continued stays empty on the follow-up slide
+        let contents = rest
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| match (i, c, &title) {
+                (_, Some(c), _) => c,
+                (0, None, Some(title)) => Content::Text(title.clone()),
+                _ => Content::Text(vec![]),
+            })
+            .collect();
+
+        Ok(Some(Slide { contents, ..slide }))
+    }
+
+    /// splits the content into the part which fits into the area
+    /// and the part which needs to be continued, if there is one.
+    /// At least one line or list item is always kept
+    fn split_content(
+        page: &mut pdf::Page,
+        content: Content,
+        args: &pdf::TextArgs,
+        item_spacing: f64,
+        config: &Config,
+    ) -> DResult<(Content, Option<Content>)> {
+        let available = args.area.size().y;
+
+        Ok(match content {
+            Content::Text(mut runs) => {
+                let at = page.fitting_text(args, &Self::text_runs(&runs, args, config)?)?;
+                let rest = split_runs(&mut runs, at);
+
+                let rest = Some(rest).filter(|r| !r.is_empty()).map(Content::Text);
+                (Content::Text(runs), rest)
+            }
+            Content::List(mut items) => {
+                let list = Self::measure_list(page, &items, args, item_spacing, config)?;
+                let item_space = Pt(args.font_size * item_spacing);
+
+                let mut height = Pt(0.0) - item_space;
+                let fitting = list
+                    .entries
+                    .iter()
+                    .take_while(|e| {
+                        height += item_space + e.height;
+                        height <= available
+                    })
+                    .count()
+                    .max(1);
+
+                let rest = items.split_off(fitting.min(items.len()));
+                let rest = Some(rest).filter(|r| !r.is_empty()).map(Content::List);
+                (Content::List(items), rest)
+            }
+            c => (c, None),
+        })
     }
 
-    fn list(
+    /// the height the content would have if it's drawn with the args
+    fn content_height(
         page: &mut pdf::Page,
-        items: Vec<(u8, String)>,
-        mut args: pdf::TextArgs,
+        content: &Content,
+        args: &pdf::TextArgs,
+        item_spacing: f64,
+        config: &Config,
+    ) -> DResult<Pt> {
+        Ok(match content {
+            Content::Text(runs) => {
+                page.measure_text(args, &Self::text_runs(runs, args, config)?)?
+                    .y
+            }
+            Content::List(items) => {
+                Self::measure_list(page, items, args, item_spacing, config)?.height
+            }
+            Content::Config(_) | Content::Image(..) => Pt(0.0),
+        })
+    }
+
+    /// searches the largest font size, which isn't bigger than the one
+    /// of the args, at which the content still fits into the area
+    fn shrink(
+        page: &mut pdf::Page,
+        content: &Content,
+        args: &mut pdf::TextArgs,
+        item_spacing: f64,
+        config: &Config,
     ) -> DResult<()> {
-        use printpdf::Pt;
-        //let ident_width = page.doc.get_width("-", args.font_size, args.font)?;
-        let ident_width = Pt(args.font_size * 1.5);
-        let orig = *args.area.origin();
+        let available = args.area.size().y;
+        if Self::content_height(page, content, args, item_spacing, config)? <= available {
+            return Ok(());
+        }
+
+        // the content fits at low but not at high
+        let mut low = MIN_FONT_SIZE.min(args.font_size);
+        let mut high = args.font_size;
+        while high - low > FONT_SIZE_PRECISION {
+            args.font_size = (low + high) / 2.0;
+            if Self::content_height(page, content, args, item_spacing, config)? <= available {
+                low = args.font_size;
+            } else {
+                high = args.font_size;
+            }
+        }
+
+        args.font_size = low;
+        Ok(())
+    }
 
-        if args.orientation != &Default::default() {
-            eprintln!("warning list are currently only supported in top-left orientation");
+    /// measures every item of the list to know the size
+    /// of the whole list before it's drawn
+    fn measure_list<'r>(
+        page: &mut pdf::Page,
+        items: &'r [ListItem],
+        args: &pdf::TextArgs<'r>,
+        item_spacing: f64,
+        config: &'r Config,
+    ) -> DResult<MeasuredList<'r>> {
+        let font_size = args.font_size;
+        let area_orig = *args.area.origin();
+        let area_size = *args.area.size();
+        let area_end = area_orig.x + area_size.x;
+        let line_height =
+            page.doc.line_height(args.font, args.variant, font_size)? * args.line_spacing;
+
+        let mut entries = Vec::with_capacity(items.len());
+        for item in items.iter() {
+            let level = config.style.list_level(item.level);
+
+            // every level is indented relative to the one above
+            let indent: f64 = (0..=item.level)
+                .map(|l| config.style.list_level(l).indent)
+                .sum();
+            let indent = Pt(font_size * indent);
+
+            // ordered items get their number instead of the bullet
+            let bullet = match item.marker.kind {
+                ListKind::Bullet => level.bullet.clone(),
+                _ => config::Bullet::Glyph(item.marker.label()),
+            };
+
+            // long numbers push the text further to the right
+            let bullet_width = match &bullet {
+                config::Bullet::Glyph(g) => {
+                    page.doc.get_width(g, font_size, args.font, args.variant)?.0
+                }
+                _ => 0.0,
+            };
+            let text_offset =
+                indent + Pt((font_size * level.spacing).max(bullet_width + font_size * 0.3));
+
+            let runs = Self::text_runs(&item.text, args, config)?;
+            let text_orig = config::Point {
+                x: area_orig.x + text_offset,
+                y: area_orig.y,
+            };
+            let size =
+                page.measure_text(&item_args(args, text_orig, area_end, area_size.y), &runs)?;
+            let text: String = item.text.iter().map(|r| r.text.as_str()).collect();
+
+            entries.push(ListEntry {
+                rtl: pdf::is_rtl(&text),
+                indent,
+                text_offset,
+                bullet,
+                color: level.color.map(|i| config.get_color(i)).transpose()?,
+                runs,
+                // the bullet needs a line even without text
+                height: if size.y > line_height {
+                    size.y
+                } else {
+                    line_height
+                },
+                width: text_offset + size.x,
+            });
         }
-        for (ident, text) in items {
-            page.new_layer("please end my suffering");
-            // the ident of the list item and drawing the symbol
-            let mut ident_pos = orig
-                + config::Point {
-                    x: ident_width * ident as f64,
-                    y: Pt(0.0),
+
+        let gaps = entries.len().saturating_sub(1) as f64;
+        let height = entries
+            .iter()
+            .fold(Pt(font_size * item_spacing) * gaps, |h, e| h + e.height);
+        let width = entries
+            .iter()
+            .map(|e| e.width)
+            .fold(Pt(0.0), |w, e| if e > w { e } else { w });
+
+        Ok(MeasuredList {
+            entries,
+            height,
+            width,
+        })
+    }
+
+    /// draws the list as one block, which is placed
+    /// inside the area according to its orientation.
+    /// The items are item_spacing times the font size apart,
+    /// returns the height of the whole list
+    fn draw_list(
+        page: &mut pdf::Page,
+        list: MeasuredList,
+        args: &pdf::TextArgs,
+        item_spacing: f64,
+    ) -> DResult<Pt> {
+        use config::{HorOrientation as Hor, VertOrientation as Vert};
+
+        let area_orig = *args.area.origin();
+        let area_size = *args.area.size();
+        let area_end = area_orig.x + area_size.x;
+        let item_space = Pt(args.font_size * item_spacing);
+
+        let left = area_orig.x
+            + match args.orientation.horizontal {
+                Hor::Left | Hor::Justify => Pt(0.0),
+                Hor::Middle => (area_size.x - list.width) / 2.0,
+                Hor::Right => area_size.x - list.width,
+            };
+        let mut top = area_orig.y
+            + match args.orientation.vertical {
+                Vert::Top => area_size.y,
+                Vert::Middle => (area_size.y + list.height) / 2.0,
+                Vert::Bottom => list.height,
+            };
+
+        // right-to-left items are mirrored, so they start at the right
+        let right = area_orig.x + area_end - left;
+
+        for entry in list.entries {
+            top -= entry.height;
+
+            let (bullet_args, text_args) = if entry.rtl {
+                let orig = config::Point {
+                    x: area_orig.x,
+                    y: top,
                 };
-            page.doc.set_lower_left(&mut args.area, ident_pos);
-            page.draw_text(&args, "-")?;
-            ident_pos.x += ident_width;
-            page.doc.set_lower_left(&mut args.area, ident_pos);
-
-            // TODO: move the area to the right according to the ident
-
-            // writing the item and move down to the next item
-            let pt_written = page.draw_text(&args, &text)?;
-            // decrease the height of the area
-            page.doc
-                .move_upper_right(&mut args.area, (Pt(0.0), Pt(0.0) - pt_written).into());
+                let bullet_args = pdf::TextArgs {
+                    orientation: &TOP_RIGHT,
+                    ..item_args(args, orig, right - entry.indent, entry.height)
+                };
+                let text_end = right - entry.text_offset;
+                (bullet_args, item_args(args, orig, text_end, entry.height))
+            } else {
+                let at = |offset| config::Point {
+                    x: left + offset,
+                    y: top,
+                };
+                (
+                    item_args(args, at(entry.indent), area_end, entry.height),
+                    item_args(args, at(entry.text_offset), area_end, entry.height),
+                )
+            };
+
+            page.draw_bullet(&bullet_args, &entry.bullet, entry.color)?;
+            page.draw_text(&text_args, &entry.runs)?;
+            top -= item_space;
         }
 
-        Ok(())
+        Ok(list.height)
+    }
+}
+
+/// splits the runs at the byte index of their joined text,
+/// the runs keep the text before it and the rest is returned
+fn split_runs(runs: &mut Vec<Run>, at: usize) -> Vec<Run> {
+    let mut start = 0;
+    for (i, run) in runs.iter_mut().enumerate() {
+        let end = start + run.text.len();
+        if at < end {
+            let tail = Run {
+                text: run.text.split_off(at - start),
+                style: run.style,
+            };
+            let mut rest = runs.split_off(i + 1);
+            rest.insert(0, tail);
+
+            // don't keep runs without any text
+            runs.retain(|r| !r.text.is_empty());
+            return rest;
+        }
+        start = end;
     }
+
+    vec![]
+}
+
+/// inserts soft hyphens into the text of the content, code is kept as it is
+fn hyphenate(content: &mut Content, lang: hypher::Lang) {
+    let runs = match content {
+        Content::Text(runs) => vec![runs],
+        Content::List(items) => items.iter_mut().map(|i| &mut i.text).collect(),
+        Content::Config(_) | Content::Image(..) => vec![],
+    };
+
+    for run in runs.into_iter().flatten().filter(|r| !r.style.code) {
+        run.text = pdf::hyphenate(&run.text, lang);
+    }
+}
+
+/// the args for a single list item inside the area of the list,
+/// which reaches from orig to the right side (end)
+fn item_args<'a>(
+    args: &pdf::TextArgs<'a>,
+    orig: config::Point<Pt>,
+    end: Pt,
+    height: Pt,
+) -> pdf::TextArgs<'a> {
+    pdf::TextArgs {
+        area: pdf::PdfRect::new(
+            orig,
+            config::Point {
+                x: end - orig.x,
+                y: height,
+            },
+        ),
+        // the items themselves are always drawn from the top-left,
+        // unless the whole list is justified
+        orientation: if args.orientation.horizontal == config::HorOrientation::Justify {
+            &TOP_JUSTIFY
+        } else {
+            &TOP_LEFT
+        },
+        ..*args
+    }
+}
+
+/// a list with all items measured
+struct MeasuredList<'r> {
+    entries: Vec<ListEntry<'r>>,
+    height: Pt,
+    /// the width from the left of the list to the end of the widest line
+    width: Pt,
+}
+
+/// a measured list item, ready to be drawn
+struct ListEntry<'r> {
+    /// the item is written right-to-left, so it's mirrored
+    rtl: bool,
+    /// distance of the bullet from the left of the list
+    /// (from the right for right-to-left items)
+    indent: Pt,
+    /// distance of the text from the left of the list
+    /// (from the right for right-to-left items)
+    text_offset: Pt,
+    bullet: config::Bullet,
+    color: Option<config::Color>,
+    runs: Vec<pdf::TextRun<'r>>,
+    height: Pt,
+    /// the width from the left of the list to the end of the widest line
+    width: Pt,
 }
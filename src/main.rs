@@ -21,8 +21,19 @@ fn main() -> Result<(), DrawError> {
         .with_templates(args.templates)
         .build(&args.doc_name);
 
-    let source = std::fs::read_to_string(args.present_file).unwrap();
-    let slides = parser::parse(&source);
+    let present_file = args.present_file;
+    let source = std::fs::read_to_string(&present_file).unwrap();
+    let slides = parser::parse(&source).unwrap_or_else(|errors| {
+        for e in errors.iter() {
+            eprintln!("{}", e.render(&source));
+        }
+        eprintln!(
+            "couldn't parse {} due to {} error(s)",
+            present_file.to_string_lossy(),
+            errors.len()
+        );
+        std::process::exit(1);
+    });
     let mut pdf = PdfMaker::with_config(&config).expect("couldn't get the pdfmaker");
 
     for slide in slides {
@@ -39,9 +50,9 @@ fn main() -> Result<(), DrawError> {
                     .flatten()
                     .expect("expected path to a style sheet");
 
-                config
-                    .change_style(path)
-                    .expect("Couldn't load style sheet");
+                if let Err(e) = config.change_style(path) {
+                    eprintln!("{}\n\tkeeping the current style", e);
+                }
             }
             _ => pdf
                 .create_slide(slide, &config)
@@ -51,15 +51,6 @@ pub trait Parser<T>: Sized {
         }
     }
 
-    /// make sure there is a certain prefix before the parsed content,
-    /// output remains unchanged
-    fn prefix<P: Parser<T>>(self, after: P) -> Prefix<Self, P> {
-        Prefix {
-            parser: self,
-            prefix: after,
-        }
-    }
-
     /// creates a new parser where either this or the other needs to parse
     /// for a valid result. Both parsers need to return the same type
     fn or<P: Parser<T, Output = Self::Output>>(self, other: P) -> Or<Self, P> {
@@ -148,25 +139,6 @@ where
     }
 }
 
-#[derive(Clone)]
-pub struct Prefix<P, Q> {
-    parser: P,
-    prefix: Q,
-}
-
-impl<P, Q, T> Parser<T> for Prefix<P, Q>
-where
-    P: Parser<T>,
-    Q: Parser<T>,
-{
-    type Output = P::Output;
-
-    fn parse(&self, input: &[T], mut offset: usize) -> ParseResult<Self::Output> {
-        offset = self.prefix.parse(input, offset)?.0;
-        self.parser.parse(input, offset)
-    }
-}
-
 #[derive(Clone)]
 pub struct Or<P, Q> {
     this: P,
@@ -250,9 +222,10 @@ pub fn eof<T: std::fmt::Debug>(input: &[T], offset: usize) -> ParseResult<()> {
     if offset >= input.len() {
         p_ok(offset, ())
     } else {
-        Err(ParseError {
-            expected: "EOF",
-            actual: format!("{:?}", input[offset]),
-        })
+        Err(ParseError::new(
+            "EOF",
+            format!("{:?}", input[offset]),
+            offset,
+        ))
     }
 }
@@ -0,0 +1,335 @@
+use super::slide::{Run, TextStyle};
+use std::borrow::Cow;
+
+/// chars which lose their special meaning when prefixed with a backslash
+const ESCAPABLE: &str = ";*-[]\"\\_`{}+";
+
+/// the piece a line with only a `+` stands for
+pub const PARAGRAPH_BREAK: Inline<'static> = Inline::Char('\n');
+
+/// the markers that surround a styled span
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mark {
+    /// `*bold*`
+    Bold,
+    /// `_italic_`
+    Italic,
+    /// `` `code` ``
+    Code,
+}
+
+impl Mark {
+    fn literal(self) -> &'static str {
+        match self {
+            Mark::Bold => "*",
+            Mark::Italic => "_",
+            Mark::Code => "`",
+        }
+    }
+
+    fn toggle(self, style: &mut TextStyle) {
+        match self {
+            Mark::Bold => style.bold = !style.bold,
+            Mark::Italic => style.italic = !style.italic,
+            Mark::Code => style.code = !style.code,
+        }
+    }
+}
+
+/// a piece of a text line, as split by the lexer
+#[derive(Debug, PartialEq, Clone)]
+pub enum Inline<'a> {
+    Text(&'a str),
+    /// a char from an escape sequence, never part of any markup
+    Char(char),
+    /// a marker that may start and/or end a styled span,
+    /// depending on the chars around it
+    Delim {
+        mark: Mark,
+        open: bool,
+        close: bool,
+    },
+    /// `{n|` starts a span in the color with the index n
+    ColorStart(usize),
+    /// `}` ends the last color span
+    ColorEnd,
+}
+
+/// splits a line of text into plain text and the markers of
+/// styled spans. Whether a marker really starts a span is
+/// decided later, when the whole paragraph is known
+pub fn split(line: &str) -> Vec<Inline<'_>> {
+    let mut pieces = vec![];
+    let mut start = 0; // start of the current plain text
+    let mut chars = line.char_indices().peekable();
+    let mut prev = None;
+
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+
+        if let Some((escaped, len)) = escape_at(&line[i..]) {
+            push_text(&mut pieces, &line[start..i]);
+            pieces.push(Inline::Char(escaped));
+
+            start = i + len;
+            skip_to(&mut chars, start);
+            prev = Some(escaped);
+            continue;
+        }
+
+        let piece = match c {
+            // code spans need to be closed on the same line,
+            // nothing inside of them is markup
+            '`' => line[i + 1..].find('`').map(|len| {
+                let inner = &line[i + 1..i + 1 + len];
+                (i + len + 2, inner)
+            }),
+            _ => None,
+        };
+
+        if let Some((end, inner)) = piece {
+            push_text(&mut pieces, &line[start..i]);
+            pieces.push(Inline::Delim {
+                mark: Mark::Code,
+                open: true,
+                close: false,
+            });
+            push_text(&mut pieces, inner);
+            pieces.push(Inline::Delim {
+                mark: Mark::Code,
+                open: false,
+                close: true,
+            });
+
+            // nothing inside the code span is markup
+            start = end;
+            skip_to(&mut chars, start);
+            prev = Some('`');
+            continue;
+        }
+
+        let marker = match c {
+            '*' | '_' => {
+                let open = matches!(next, Some(n) if !n.is_whitespace());
+                let close = matches!(prev, Some(p) if !p.is_whitespace());
+                // snake_case is no italic text
+                let intraword = c == '_'
+                    && matches!(prev, Some(p) if p.is_alphanumeric())
+                    && matches!(next, Some(n) if n.is_alphanumeric());
+                let mark = if c == '*' { Mark::Bold } else { Mark::Italic };
+
+                if (open || close) && !intraword {
+                    Some((Inline::Delim { mark, open, close }, i + 1))
+                } else {
+                    None
+                }
+            }
+            '{' => {
+                let digits = line[i + 1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .filter(|len| *len > 0 && line[i + 1 + len..].starts_with('|'));
+
+                digits.map(|len| {
+                    let idx = line[i + 1..i + 1 + len].parse().unwrap_or(usize::MAX);
+                    (Inline::ColorStart(idx), i + len + 2)
+                })
+            }
+            '}' => Some((Inline::ColorEnd, i + 1)),
+            _ => None,
+        };
+
+        if let Some((marker, end)) = marker {
+            push_text(&mut pieces, &line[start..i]);
+            pieces.push(marker);
+            start = end;
+            skip_to(&mut chars, start);
+        }
+
+        prev = Some(c);
+    }
+
+    push_text(&mut pieces, &line[start..]);
+    pieces
+}
+
+/// advances the chars until the byte index `end`
+fn skip_to<I: Iterator<Item = (usize, char)>>(chars: &mut std::iter::Peekable<I>, end: usize) {
+    while let Some((i, _)) = chars.peek() {
+        if *i >= end {
+            break;
+        }
+        chars.next();
+    }
+}
+
+/// parses the escape sequence at the start of the text
+/// and returns the escaped char with the length of the sequence.
+/// Unknown escapes are not escapes, so the backslash stays
+pub fn escape_at(text: &str) -> Option<(char, usize)> {
+    let rest = text.strip_prefix('\\')?;
+    let c = rest.chars().next()?;
+
+    if ESCAPABLE.contains(c) {
+        return Some((c, 1 + c.len_utf8()));
+    }
+
+    // unicode code points: \u{1F600}
+    let hex = rest.strip_prefix("u{")?;
+    let len = hex.find('}')?;
+    let code = u32::from_str_radix(&hex[..len], 16).ok()?;
+
+    std::char::from_u32(code).map(|c| (c, len + 4))
+}
+
+/// replaces all escape sequences inside the text
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return text.into();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        match escape_at(&rest[i..]) {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[i + len..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out.into()
+}
+
+fn push_text<'a>(pieces: &mut Vec<Inline<'a>>, text: &'a str) {
+    if !text.is_empty() {
+        pieces.push(Inline::Text(text));
+    }
+}
+
+/// joins the lines of a paragraph with spaces and
+/// converts them into styled runs
+pub fn runs(lines: Vec<Vec<Inline<'_>>>) -> Vec<Run> {
+    let pieces: Vec<_> = join(lines).collect();
+    let active = pair_markers(&pieces);
+
+    let mut runs: Vec<Run> = vec![];
+    let mut style = TextStyle::default();
+    let mut colors = vec![];
+
+    for (piece, active) in pieces.iter().zip(active) {
+        let text = match (piece, active) {
+            (Inline::Delim { mark, .. }, true) => {
+                mark.toggle(&mut style);
+                continue;
+            }
+            (Inline::ColorStart(idx), true) => {
+                colors.push(style.color);
+                style.color = Some(*idx);
+                continue;
+            }
+            (Inline::ColorEnd, true) => {
+                style.color = colors.pop().flatten();
+                continue;
+            }
+            (piece, _) => literal(piece),
+        };
+
+        match runs.last_mut() {
+            Some(run) if run.style == style => run.text.push_str(&text),
+            _ => runs.push(Run {
+                text: text.into_owned(),
+                style,
+            }),
+        }
+    }
+
+    runs
+}
+
+/// joins the lines with spaces, except around paragraph breaks
+fn join(lines: Vec<Vec<Inline<'_>>>) -> impl Iterator<Item = Inline<'_>> {
+    let mut after_break = true;
+
+    lines.into_iter().flat_map(move |line| {
+        let is_break = line == [PARAGRAPH_BREAK];
+        let space = match after_break || is_break {
+            true => None,
+            false => Some(Inline::Text(" ")),
+        };
+
+        after_break = is_break;
+        space.into_iter().chain(line)
+    })
+}
+
+/// the text a piece stands for if it isn't used as a marker
+fn literal<'a>(piece: &Inline<'a>) -> Cow<'a, str> {
+    match piece {
+        Inline::Text(t) => (*t).into(),
+        Inline::Char(c) => c.to_string().into(),
+        Inline::Delim { mark, .. } => mark.literal().into(),
+        Inline::ColorStart(idx) => format!("{{{}|", idx).into(),
+        Inline::ColorEnd => "}".into(),
+    }
+}
+
+/// decides for every marker if it's used to start or end a span,
+/// markers without a partner are treated as text
+fn pair_markers(pieces: &[Inline]) -> Vec<bool> {
+    let mut active = vec![false; pieces.len()];
+    // indices of the markers which are still open
+    let mut open: Vec<usize> = vec![];
+
+    for (i, piece) in pieces.iter().enumerate() {
+        match piece {
+            Inline::Delim {
+                mark,
+                open: o,
+                close,
+            } => {
+                let partner = if *close {
+                    open.iter().rposition(|j| match &pieces[*j] {
+                        Inline::Delim { mark: m, .. } => m == mark,
+                        _ => false,
+                    })
+                } else {
+                    None
+                };
+
+                match partner {
+                    Some(p) => {
+                        // everything opened inside the span can't be closed anymore
+                        active[open[p]] = true;
+                        active[i] = true;
+                        open.truncate(p);
+                    }
+                    None if *o => open.push(i),
+                    None => (),
+                }
+            }
+            Inline::ColorStart(_) => open.push(i),
+            Inline::ColorEnd => {
+                let partner = open
+                    .iter()
+                    .rposition(|j| matches!(pieces[*j], Inline::ColorStart(_)));
+
+                if let Some(p) = partner {
+                    active[open[p]] = true;
+                    active[i] = true;
+                    open.truncate(p);
+                }
+            }
+            Inline::Text(_) | Inline::Char(_) => (),
+        }
+    }
+
+    active
+}
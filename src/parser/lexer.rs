@@ -1,7 +1,9 @@
-use super::Token;
+use super::tokens::{self, Position, Spanned, Token};
 use regex::Regex;
 use std::iter::Iterator;
 
+/// creates a token from the captures and the width of the
+/// indentation in front of it
 pub type TokenCreator = dyn for<'s> Fn(usize, regex::Captures<'s>) -> Token<'s> + Sync;
 
 /// iterator that iterates over all the tokens
@@ -13,10 +15,12 @@ pub struct Lexer<'a, 's> {
     pub whitespace: &'a Regex,
     pub invalid: Token<'s>,
     pub source: &'s str,
+    /// the position of the remaining source
+    pub pos: Position,
 }
 
 impl<'a, 's> Iterator for Lexer<'a, 's> {
-    type Item = Token<'s>;
+    type Item = Spanned<'s>;
 
     fn next(&mut self) -> Option<Self::Item> {
         // nothing more to tokenize
@@ -29,11 +33,12 @@ impl<'a, 's> Iterator for Lexer<'a, 's> {
         // skip the comments
         loop {
             // skip whitespace
-            indent = match self.whitespace.find(self.source) {
+            let len = match self.whitespace.find(self.source) {
                 Some(m) if m.start() == 0 => m.end(),
                 _ => 0,
             };
-            self.update_pos(indent);
+            indent = tokens::indent_width(&self.source[..len]);
+            self.update_pos(len);
 
             match self.comment.find(self.source) {
                 Some(m) if m.start() == 0 => self.update_pos(m.end()),
@@ -46,8 +51,7 @@ impl<'a, 's> Iterator for Lexer<'a, 's> {
             match re.find(self.source) {
                 Some(m) if m.start() == 0 => {
                     let tok = tok.clone();
-                    self.update_pos(m.end());
-                    return Some(tok);
+                    return Some(self.spanned(tok, m.end()));
                 }
                 _ => (),
             }
@@ -62,18 +66,26 @@ impl<'a, 's> Iterator for Lexer<'a, 's> {
                 }
 
                 let tok = tok_fn(indent, c);
-                self.update_pos(full.end());
-                return Some(tok);
+                return Some(self.spanned(tok, full.end()));
             }
         }
 
-        self.source = "";
-        Some(self.invalid.clone())
+        let rest = self.source.len();
+        Some(self.spanned(self.invalid.clone(), rest))
     }
 }
 
 impl<'a, 's> Lexer<'a, 's> {
     fn update_pos(&mut self, pos: usize) {
+        self.pos.advance(&self.source[..pos]);
         self.source = &self.source[pos..];
     }
+
+    /// wraps the token with its current position and
+    /// moves behind it
+    fn spanned(&mut self, token: Token<'s>, len: usize) -> Spanned<'s> {
+        let pos = self.pos;
+        self.update_pos(len);
+        Spanned { token, pos, len }
+    }
 }
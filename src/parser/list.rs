@@ -0,0 +1,185 @@
+use super::slide::{Content, ListItem, ListKind, ListMarker, Run, Slide};
+
+/// parses the marker in front of a list item,
+/// ex. "-", "3." or "iv)"
+pub fn marker(s: &str) -> ListMarker {
+    let (number, delim) = s.split_at(s.len() - 1);
+    let delim = delim.chars().next().unwrap_or('.');
+    let only = |pred: fn(char) -> bool| number.chars().all(pred);
+
+    let (kind, number) = if s == "-" || s == "*" {
+        (ListKind::Bullet, None)
+    } else if number == "#" {
+        // continues the last list
+        (ListKind::Decimal, None)
+    } else if only(|c| c.is_ascii_digit()) {
+        (ListKind::Decimal, number.parse().ok())
+    } else if number == "i" || number.len() > 1 && only(|c| "ivxlcdm".contains(c)) {
+        (ListKind::LowerRoman, from_roman(number))
+    } else if number == "I" || number.len() > 1 && only(|c| "IVXLCDM".contains(c)) {
+        (ListKind::UpperRoman, from_roman(number))
+    } else {
+        let c = number.chars().next().unwrap_or('a');
+        let kind = if c.is_ascii_uppercase() {
+            ListKind::UpperAlpha
+        } else {
+            ListKind::LowerAlpha
+        };
+        (
+            kind,
+            Some((c.to_ascii_lowercase() as u8 - b'a') as usize + 1),
+        )
+    };
+
+    ListMarker {
+        kind,
+        number,
+        delim,
+    }
+}
+
+fn from_roman(s: &str) -> Option<usize> {
+    let value = |c: char| match c.to_ascii_lowercase() {
+        'i' => 1,
+        'v' => 5,
+        'x' => 10,
+        'l' => 50,
+        'c' => 100,
+        'd' => 500,
+        _ => 1000,
+    };
+
+    let values: Vec<usize> = s.chars().map(value).collect();
+    let sum = values.iter().enumerate().fold(0, |sum, (i, v)| {
+        // a smaller value before a bigger one is subtracted
+        match values.get(i + 1) {
+            Some(next) if next > v => sum - *v as isize,
+            _ => sum + *v as isize,
+        }
+    });
+
+    Some(sum).filter(|s| *s > 0).map(|s| s as usize)
+}
+
+/// turns the indentation of the items into nesting levels.
+/// An item is nested one level deeper than the closest item
+/// above it with a smaller indentation, so the amount of
+/// whitespace used for each level doesn't matter
+pub fn nest(items: Vec<((usize, ListMarker), Vec<Run>)>) -> Vec<ListItem> {
+    // the indentation of every level that is still open
+    let mut indents: Vec<usize> = vec![];
+
+    items
+        .into_iter()
+        .map(|((indent, marker), text)| {
+            while matches!(indents.last(), Some(i) if *i > indent) {
+                indents.pop();
+            }
+            if indents.last() != Some(&indent) {
+                indents.push(indent);
+            }
+
+            ListItem {
+                level: indents.len() - 1,
+                marker,
+                text,
+            }
+        })
+        .collect()
+}
+
+/// the number sequence of one nesting level
+struct Sequence {
+    level: usize,
+    kind: ListKind,
+    delim: char,
+    next: usize,
+}
+
+/// numbers all ordered list items of the presentation.
+/// The first item of a list decides where the numbering starts,
+/// every following item on the same level is just counted up.
+/// `#.` continues the last list of the same depth, even on another slide
+pub fn number(slides: &mut [Slide]) {
+    // the sequence each depth ended with
+    let mut last: Vec<Option<Sequence>> = vec![];
+
+    let lists = slides
+        .iter_mut()
+        .flat_map(|s| s.contents.iter_mut())
+        .filter_map(|c| match c {
+            Content::List(items) => Some(items),
+            _ => None,
+        });
+
+    for items in lists {
+        let mut stack: Vec<Sequence> = vec![];
+
+        for item in items.iter_mut() {
+            while matches!(stack.last(), Some(s) if s.level > item.level) {
+                end(&mut last, &mut stack);
+            }
+
+            let marker = &mut item.marker;
+            if marker.kind == ListKind::Bullet {
+                continue;
+            }
+
+            let same = match stack.last() {
+                Some(s) if s.level == item.level => {
+                    marker.number.is_none() || s.kind == marker.kind && s.delim == marker.delim
+                }
+                _ => false,
+            };
+
+            if !same {
+                // a new list on this level, replace an old one
+                if matches!(stack.last(), Some(s) if s.level == item.level) {
+                    end(&mut last, &mut stack);
+                }
+
+                let start = match (marker.number, last.get(stack.len())) {
+                    (Some(n), _) => Sequence {
+                        level: item.level,
+                        kind: marker.kind,
+                        delim: marker.delim,
+                        next: n,
+                    },
+                    (None, Some(Some(s))) => Sequence {
+                        level: item.level,
+                        delim: marker.delim,
+                        ..*s
+                    },
+                    (None, _) => Sequence {
+                        level: item.level,
+                        kind: ListKind::Decimal,
+                        delim: marker.delim,
+                        next: 1,
+                    },
+                };
+                stack.push(start);
+            }
+
+            // PANICS: there is always a sequence on the stack
+            let seq = stack.last_mut().unwrap();
+            marker.kind = seq.kind;
+            marker.delim = seq.delim;
+            marker.number = Some(seq.next);
+            seq.next += 1;
+        }
+
+        while !stack.is_empty() {
+            end(&mut last, &mut stack);
+        }
+    }
+}
+
+/// removes the innermost sequence and remembers it for
+/// lists which continue it
+fn end(last: &mut Vec<Option<Sequence>>, stack: &mut Vec<Sequence>) {
+    let depth = stack.len() - 1;
+    if last.len() <= depth {
+        last.resize_with(depth + 1, || None);
+    }
+    last[depth] = stack.pop();
+}
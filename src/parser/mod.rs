@@ -1,24 +1,44 @@
-use tokens::Token;
+use tokens::{Spanned, Token};
 
 mod combinators;
+mod inline;
 mod lexer;
+mod list;
 mod parse_error;
 mod slide;
 mod slides;
 mod tokens;
 
+pub use parse_error::ParseError;
 pub use slide::*;
 
-/// takes a reference to some source and returns the lazily
-/// parsed slides
-pub fn parse(source: &'_ str) -> impl Iterator<Item = Slide> + '_ {
-    let tokens = lexer::Lexer {
+/// takes a reference to some source and returns the parsed slides,
+/// or every error found inside the source if any slide is invalid
+pub fn parse(source: &'_ str) -> Result<Vec<Slide>, Vec<ParseError<'static>>> {
+    let mut errors = vec![];
+    let mut slides: Vec<_> = slides::lazy_parser(lex(source))
+        .filter_map(|s| s.map_err(|e| errors.push(e)).ok())
+        //.inspect(|s| println!("slide: {:?}", s))
+        .collect();
+
+    if errors.is_empty() {
+        list::number(&mut slides);
+        Ok(slides)
+    } else {
+        Err(errors)
+    }
+}
+
+/// splits the source into tokens together with their positions
+fn lex(source: &'_ str) -> Vec<Spanned<'_>> {
+    lexer::Lexer {
         source,
         no_captures: tokens::NON_CAPTURES.as_ref(),
         captures: tokens::CAPTURES.as_ref(),
         comment: &tokens::COMMENT,
         whitespace: &tokens::WHITESPACE,
         invalid: Token::Illegal,
+        pos: tokens::Position::start(),
     }
     // remove repeated linefeeds
     .filter({
@@ -26,19 +46,206 @@ pub fn parse(source: &'_ str) -> impl Iterator<Item = Slide> + '_ {
         let mut last = false;
         move |t| {
             last = next;
-            next = t == &Token::Linefeed;
+            next = t.token == Token::Linefeed;
             !(last && next)
         }
     })
     //.inspect(|t| eprintln!("{:?}", t))
-    .collect();
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokens::Position;
+
+    #[test]
+    fn position_advances_over_lines() {
+        let mut pos = Position::start();
+        pos.advance("--- Title\n\ttäxt");
+
+        assert_eq!(pos.offset, 16);
+        assert_eq!(pos.line, 2);
+        assert_eq!(pos.column, 6);
+    }
+
+    #[test]
+    fn error_points_to_token() {
+        let source = "--- Title\n\nHello\n\n--- Head_Cont [x]\n";
+        let error = super::slides::lazy_parser(super::lex(source))
+            .find_map(Result::err)
+            .expect("text behind the header");
+
+        assert_eq!(error.pos.map(|p| (p.line, p.column)), Some((5, 15)));
+        assert_eq!(
+            error.slide.as_ref().map(|s| s.0.as_str()),
+            Some("Head_Cont")
+        );
+        assert!(error
+            .render(source)
+            .ends_with("5 | --- Head_Cont [x]\n  |               ^^^\n"));
+    }
+
+    #[test]
+    fn errors_are_collected_per_slide() {
+        let source = "--- Title a\n\nb\n\n--- Head_Cont\n\nfine\n\n--- Title c\n\nd\n";
+        let errors = super::parse(source).expect_err("both headers are followed by text");
+        let lines: Vec<_> = errors
+            .iter()
+            .filter_map(|e| e.pos)
+            .map(|p| p.line)
+            .collect();
+
+        assert_eq!(lines, [1, 9]);
+    }
+
+    #[test]
+    fn inline_styles_span_lines() {
+        use super::{Content, Run, TextStyle};
+
+        let source = "--- Title\n\n*bold\ntext* and {2|`a*b`} snake_case\n";
+        let slides = super::parse(source).unwrap();
+        let runs = match &slides[0].contents[0] {
+            Content::Text(runs) => runs,
+            c => panic!("expected text, found {:?}", c),
+        };
+
+        let bold = TextStyle {
+            bold: true,
+            ..Default::default()
+        };
+        let code = TextStyle {
+            code: true,
+            color: Some(2),
+            ..Default::default()
+        };
+        let run = |text: &str, style| Run {
+            text: text.into(),
+            style,
+        };
+
+        assert_eq!(
+            runs,
+            &[
+                run("bold text", bold),
+                run(" and ", TextStyle::default()),
+                run("a*b", code),
+                run(" snake_case", TextStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_are_plain_text() {
+        use super::Content;
+
+        let source = "--- Two_Hor\n\n\\; not a comment\n\n\\* not \\*a list\\* item \\u{e4} \\q\n\n\"a\\\"b\"\n";
+        let slides = super::parse(source).unwrap();
+        let texts: Vec<_> = slides[0]
+            .contents
+            .iter()
+            .map(|c| match c {
+                Content::Text(runs) => runs.iter().map(|r| r.text.as_str()).collect::<String>(),
+                Content::Config(p) => p.to_string_lossy().into_owned(),
+                c => panic!("unexpected content {:?}", c),
+            })
+            .collect();
+
+        assert_eq!(
+            texts,
+            ["; not a comment", "* not *a list* item \u{e4} \\q", "a\"b"]
+        );
+    }
+
+    #[test]
+    fn brackets_are_text_outside_of_images() {
+        use super::Content;
+
+        let source =
+            "--- Two_Hor\n\n[WIP] see [1] and \"quotes\"\n\n [a tree] \"assets/tree.jpg\"\n";
+        let slides = super::parse(source).unwrap();
 
-    slides::lazy_parser(tokens).map(|s| match s {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("{:?}", e);
-            std::process::exit(1);
+        match &slides[0].contents[..] {
+            [Content::Text(runs), Content::Image(desc, path)] => {
+                assert_eq!(runs[0].text, "[WIP] see [1] and \"quotes\"");
+                assert_eq!(desc, "a tree");
+                assert_eq!(path.to_str(), Some("assets/tree.jpg"));
+            }
+            c => panic!("unexpected contents {:?}", c),
         }
-    })
-    //.inspect(|s| println!("slide: {:?}", s))
+    }
+
+    #[test]
+    fn lists_are_numbered() {
+        use super::Content;
+
+        let source =
+            "--- Two_Hor\n\n3. a\n1. b\n  i) c\n  i) d\n1. e\n- f\n\n--- Two_Hor\n\n#. g\n";
+        let slides = super::parse(source).unwrap();
+        let labels: Vec<_> = slides
+            .iter()
+            .flat_map(|s| s.contents.iter())
+            .flat_map(|c| match c {
+                Content::List(items) => items.iter().map(|i| i.marker.label()),
+                c => panic!("unexpected content {:?}", c),
+            })
+            .collect();
+
+        assert_eq!(labels, ["3.", "4.", "i)", "ii)", "5.", "-", "6."]);
+    }
+
+    #[test]
+    fn initials_are_no_list_markers() {
+        use super::Content;
+
+        let source = "--- Two_Hor\n\nA. Lincoln was here\nI. Newton too, it was mild. weather\n";
+        let slides = super::parse(source).unwrap();
+
+        match &slides[0].contents[..] {
+            [Content::Text(runs)] => assert_eq!(
+                runs[0].text,
+                "A. Lincoln was here I. Newton too, it was mild. weather"
+            ),
+            c => panic!("unexpected content {:?}", c),
+        }
+    }
+
+    #[test]
+    fn list_levels_follow_indentation() {
+        use super::Content;
+
+        let source = "--- Two_Hor\n\n- a\n   - b\n\t- c\n - d\n- e\n";
+        let slides = super::parse(source).unwrap();
+        let levels: Vec<_> = match &slides[0].contents[0] {
+            Content::List(items) => items.iter().map(|i| i.level).collect(),
+            c => panic!("unexpected content {:?}", c),
+        };
+
+        assert_eq!(levels, [0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn escaped_line_feed_starts_paragraph() {
+        use super::Content;
+
+        let source = "--- Two_Hor\n\nfirst\\u{a}second \\ third\n";
+        let slides = super::parse(source).unwrap();
+
+        match &slides[0].contents[0] {
+            Content::Text(runs) => assert_eq!(runs[0].text, "first\nsecond \\ third"),
+            c => panic!("unexpected content {:?}", c),
+        }
+    }
+
+    #[test]
+    fn plus_line_starts_paragraph() {
+        use super::Content;
+
+        let source = "--- Two_Hor\n\nfirst\nline\n+\nsecond\n\\+\n";
+        let slides = super::parse(source).unwrap();
+
+        match &slides[0].contents[0] {
+            Content::Text(runs) => assert_eq!(runs[0].text, "first line\nsecond +"),
+            c => panic!("unexpected content {:?}", c),
+        }
+    }
 }
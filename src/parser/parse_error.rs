@@ -1,12 +1,84 @@
+use super::tokens::Position;
 use std::{
     error::Error,
-    fmt::{Display, Error as FmtError, Formatter},
+    fmt::{Display, Error as FmtError, Formatter, Write},
 };
 
 #[derive(Debug)]
 pub struct ParseError<'a> {
     pub expected: &'a str,
     pub actual: String,
+    /// index of the token the error occurred at
+    pub offset: usize,
+    /// position of that token inside the source,
+    /// None if the error occurred at the end of the source
+    pub pos: Option<Position>,
+    /// length of the token in bytes
+    pub len: usize,
+    /// the kind and line of the slide header the error belongs to
+    pub slide: Option<(String, usize)>,
+}
+
+impl<'a> ParseError<'a> {
+    /// creates an error which isn't located inside the source yet
+    pub fn new(expected: &'a str, actual: String, offset: usize) -> Self {
+        Self {
+            expected,
+            actual,
+            offset,
+            pos: None,
+            len: 0,
+            slide: None,
+        }
+    }
+
+    /// renders the error together with the offending line
+    /// of the source and a caret pointing to the token
+    pub fn render(&self, source: &str) -> String {
+        let pos = self.pos.unwrap_or_else(|| Position::end_of(source));
+
+        // the whole line the error is in
+        let line_start = source[..pos.offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[pos.offset..]
+            .find('\n')
+            .map_or(source.len(), |i| pos.offset + i);
+        let line = &source[line_start..line_end];
+
+        // underline the token, but only inside the line
+        let width = source[pos.offset..(pos.offset + self.len).min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        // keep tabs, so the caret lines up with the token
+        let pad: String = source[line_start..pos.offset]
+            .chars()
+            .map(|c| if c == '\t' { c } else { ' ' })
+            .collect();
+        let number = pos.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        let mut out = format!("error: {}\n", self);
+        // writing into a String can't fail
+        let _ = write!(
+            out,
+            "{}--> line {}, column {}",
+            gutter, pos.line, pos.column
+        );
+        if let Some((kind, line)) = &self.slide {
+            let _ = write!(out, " in slide `{}` (line {})", kind, line);
+        }
+        let _ = write!(
+            out,
+            "\n{gutter} |\n{number} | {line}\n{gutter} | {pad}{caret}\n",
+            gutter = gutter,
+            number = number,
+            line = line,
+            pad = pad,
+            caret = "^".repeat(width),
+        );
+
+        out
+    }
 }
 
 impl<'a> Display for ParseError<'a> {
@@ -4,12 +4,124 @@ use std::path::PathBuf;
 pub struct Slide {
     pub kind: String,
     pub contents: Vec<Content>,
+    /// the line of the slide header inside the source
+    pub line: usize,
+}
+
+/// the inline style of a piece of text
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    /// index into the colors of the style
+    pub color: Option<usize>,
+}
+
+/// a piece of text with the same style all the way through
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+/// how the items of a list are marked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListKind {
+    Bullet,
+    /// 1. 2. 3.
+    Decimal,
+    /// a) b) c)
+    LowerAlpha,
+    /// A) B) C)
+    UpperAlpha,
+    /// i) ii) iii)
+    LowerRoman,
+    /// I) II) III)
+    UpperRoman,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListMarker {
+    pub kind: ListKind,
+    /// the number of the item, none for bullets.
+    /// Before the lists are numbered, it's the number written
+    /// in the source or none for `#.`, which continues the last list
+    pub number: Option<usize>,
+    /// the char behind the number, '.' or ')'
+    pub delim: char,
+}
+
+impl ListMarker {
+    /// the text drawn in front of the item
+    pub fn label(&self) -> String {
+        let n = match self.number {
+            Some(n) if self.kind != ListKind::Bullet => n,
+            _ => return String::from("-"),
+        };
+
+        let number = match self.kind {
+            ListKind::LowerAlpha => to_alpha(n),
+            ListKind::UpperAlpha => to_alpha(n).to_uppercase(),
+            ListKind::LowerRoman => to_roman(n),
+            ListKind::UpperRoman => to_roman(n).to_uppercase(),
+            _ => n.to_string(),
+        };
+
+        format!("{}{}", number, self.delim)
+    }
+}
+
+/// 1 -> a, 26 -> z, 27 -> aa
+fn to_alpha(mut n: usize) -> String {
+    let mut letters = vec![];
+    while n > 0 {
+        n -= 1;
+        letters.push((b'a' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    letters.into_iter().rev().collect()
+}
+
+fn to_roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS.iter() {
+        while n >= *value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    roman
+}
+
+#[derive(Debug)]
+pub struct ListItem {
+    /// the nesting level of the item, starting at 0
+    pub level: usize,
+    pub marker: ListMarker,
+    pub text: Vec<Run>,
 }
 
 #[derive(Debug)]
 pub enum Content {
-    Text(String),
+    Text(Vec<Run>),
     Config(PathBuf),
     Image(String, PathBuf),
-    List(Vec<(u8, String)>),
+    List(Vec<ListItem>),
 }
@@ -1,60 +1,55 @@
 use super::{
     combinators,
     combinators::Parser,
+    inline, list,
     parse_error::ParseError,
-    slide::{Content, Slide},
-    tokens::Token,
+    slide::{Content, ListMarker, Slide},
+    tokens::{Spanned, Token},
 };
 
-use std::path::Path;
+use std::path::PathBuf;
 
 macro_rules! token_fn {
     ($name:ident, $ret_ty:ty, $pat:pat => $ret:expr) => {
         /// tries to get the token, if the token is
         /// not found it creates a nice error
-        fn $name<'s>(input: &[Token<'s>], offset: usize) -> combinators::ParseResult<$ret_ty> {
-            match input.get(offset) {
+        fn $name<'s>(input: &[Spanned<'s>], offset: usize) -> combinators::ParseResult<$ret_ty> {
+            match input.get(offset).map(|s| &s.token) {
                 Some($pat) => combinators::p_ok(offset + 1, $ret),
-                Some(t) => Err(ParseError {
-                    actual: format!("{:?}", t),
-                    expected: stringify!($pat),
-                }),
-                None => Err(ParseError {
-                    actual: String::from("EOF"),
-                    expected: stringify!($pat),
-                }),
+                Some(t) => Err(ParseError::new(
+                    stringify!($pat),
+                    format!("{:?}", t),
+                    offset,
+                )),
+                None => Err(ParseError::new(
+                    stringify!($pat),
+                    String::from("EOF"),
+                    offset,
+                )),
             }
         }
     };
 }
 
 token_fn!(identifier, &'s str, Token::Identifier(t) => t);
-token_fn!(text, &'s str, Token::Text(t) => t);
-token_fn!(path, &'s Path, Token::Path(p) => p);
-token_fn!(list_pre, u8, Token::ListPre(i) => *i);
-token_fn!(right_bracket, (), Token::SqrBracketRight => ());
-token_fn!(left_bracket, (), Token::SqrBracketLeft => ());
+token_fn!(text, Vec<inline::Inline<'s>>, Token::Text(t) => t.clone());
+token_fn!(path, PathBuf, Token::Path(p) => p.to_path_buf());
+token_fn!(list_pre, (usize, ListMarker), Token::ListPre(i, m) => (*i, m.clone()));
+token_fn!(image, Content, Token::Image(desc, path) => Content::Image(desc.to_string(), path.to_path_buf()));
 token_fn!(line_feed, (), Token::Linefeed => ());
 
-fn construct_slide_parser<'s>() -> impl Parser<Token<'s>, Output = Slide> {
-    let text = text
+fn construct_slide_parser<'s>() -> impl Parser<Spanned<'s>, Output = Slide> {
+    let text = text.many();
+    let list = list_pre
+        .and(text.clone().process(inline::runs))
         .many()
-        .process(|v| v.into_iter().intersperse(" ").collect());
-    let list = list_pre.and(text.clone()).many().process(Content::List);
-
-    // TODO: fix problem where you can't write ] in normal text
-    let image = text
-        .clone()
-        .prefix(left_bracket)
-        .suffix(right_bracket)
-        .and(path)
-        .process(|(desc, path)| Content::Image(desc, path.into()));
+        .process(|items| Content::List(list::nest(items)));
 
     let content = path
-        .process(|p| Content::Config(p.into()))
+        .process(Content::Config)
         .or(image)
         .or(list)
-        .or(text.process(Content::Text))
+        .or(text.process(|t| Content::Text(inline::runs(t))))
         .suffix(line_feed.or(combinators::eof));
     //.inspect(|c| eprintln!("found Content: {:?}", c));
 
@@ -64,11 +59,45 @@ fn construct_slide_parser<'s>() -> impl Parser<Token<'s>, Output = Slide> {
         .process(|(kind, content)| Slide {
             kind: kind.into(),
             contents: content,
+            // set by the caller, who knows the position of the header
+            line: 0,
         })
 }
 
+/// adds the position of the offending token and the header
+/// of the slide it belongs to to the error
+fn locate(mut error: ParseError<'static>, tokens: &[Spanned]) -> ParseError<'static> {
+    if let Some(t) = tokens.get(error.offset) {
+        error.pos = Some(t.pos);
+        error.len = t.len;
+    }
+
+    // the last header before the error
+    error.slide = tokens[..error.offset.min(tokens.len())]
+        .iter()
+        .rev()
+        .find_map(|t| match t.token {
+            Token::Identifier(kind) => Some((String::from(kind), t.pos.line)),
+            _ => None,
+        });
+
+    error
+}
+
+/// index of the next slide header (`--- Kind`) starting at `from`,
+/// used to resynchronise after an error
+fn next_header(tokens: &[Spanned], from: usize) -> usize {
+    tokens
+        .iter()
+        .skip(from)
+        .position(|t| matches!(t.token, Token::Identifier(_)))
+        .map_or(tokens.len(), |i| from + i)
+}
+
+/// parses one slide after another, if a slide can't be parsed
+/// the error is returned and parsing continues at the next slide header
 pub fn lazy_parser(
-    tokens: Vec<Token<'_>>,
+    tokens: Vec<Spanned<'_>>,
 ) -> impl Iterator<Item = Result<Slide, ParseError<'static>>> + '_ {
     let parser = construct_slide_parser();
     let mut offset = 0;
@@ -76,9 +105,13 @@ pub fn lazy_parser(
     std::iter::from_fn(move || {
         if offset < tokens.len() {
             match parser.parse(&tokens, offset) {
-                Err(e) => Some(Err(e)),
+                Err(e) => {
+                    offset = next_header(&tokens, offset + 1);
+                    Some(Err(locate(e, &tokens)))
+                }
 
-                Ok((off, slide)) => {
+                Ok((off, mut slide)) => {
+                    slide.line = tokens[offset].pos.line;
                     offset = off;
                     Some(Ok(slide))
                 }
@@ -1,17 +1,88 @@
-use super::lexer;
+use super::{inline, lexer, list, slide::ListMarker};
 use regex::{Captures, Regex};
-use std::path::Path;
+use std::{borrow::Cow, fmt, path::Path};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token<'a> {
     Illegal,
     Linefeed,
-    SqrBracketLeft,
-    SqrBracketRight,
-    Path(&'a Path),
-    Text(&'a str),
+    /// `[description]"path"`
+    Image(Cow<'a, str>, Cow<'a, Path>),
+    Path(Cow<'a, Path>),
+    Text(Vec<inline::Inline<'a>>),
     Identifier(&'a str),
-    ListPre(u8),
+    /// the width of the indentation in columns and the marker
+    ListPre(usize, ListMarker),
+}
+
+/// a position inside the source of a presentation
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    /// byte offset from the start of the source
+    pub offset: usize,
+    /// line number, starting at 1
+    pub line: usize,
+    /// column in chars, starting at 1
+    pub column: usize,
+}
+
+impl Position {
+    /// the position of the first char of a source
+    pub fn start() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// the position right behind the last char of the source
+    pub fn end_of(source: &str) -> Self {
+        let mut pos = Self::start();
+        pos.advance(source);
+        pos
+    }
+
+    /// moves the position behind the consumed text
+    pub fn advance(&mut self, consumed: &str) {
+        self.offset += consumed.len();
+
+        match consumed.rfind('\n') {
+            Some(i) => {
+                self.line += consumed.matches('\n').count();
+                self.column = consumed[i + 1..].chars().count() + 1;
+            }
+            None => self.column += consumed.chars().count(),
+        }
+    }
+}
+
+/// the width of some indentation in columns,
+/// tabs go to the next multiple of the tab width
+pub fn indent_width(indent: &str) -> usize {
+    const TAB_WIDTH: usize = 4;
+
+    indent.chars().fold(0, |width, c| match c {
+        '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+        _ => width + 1,
+    })
+}
+
+/// a token together with the place it was found at
+#[derive(PartialEq, Clone)]
+pub struct Spanned<'a> {
+    pub token: Token<'a>,
+    pub pos: Position,
+    /// length of the token inside the source in bytes
+    pub len: usize,
+}
+
+// only the token is interesting when printing errors,
+// the position is reported separately
+impl<'a> fmt::Debug for Spanned<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.token.fmt(f)
+    }
 }
 
 fn regex(re: &str) -> Regex {
@@ -22,32 +93,58 @@ lazy_static::lazy_static! {
     pub static ref COMMENT: Regex = regex(r";.*\n");
     pub static ref WHITESPACE: Regex = regex(r"[^\S\n]*");
 
-    pub static ref NON_CAPTURES: [(Regex, Token<'static>); 3] = [
-        (regex(r"\["), Token::SqrBracketLeft),
-        (regex(r"\]"), Token::SqrBracketRight),
+    pub static ref NON_CAPTURES: [(Regex, Token<'static>); 1] = [
         (regex("\n"), Token::Linefeed),
     ];
 
-    pub static ref CAPTURES: [(Regex, &'static lexer::TokenCreator); 4] = [
+    // images and paths need to be the only thing on their line,
+    // otherwise brackets and quotes are just text. Letters and roman
+    // numbers only mark list items with a ")", so a sentence
+    // starting with an initial like "A. Lincoln" stays text.
+    // A line with only a "+" starts a new paragraph in the text
+    pub static ref CAPTURES: [(Regex, &'static lexer::TokenCreator); 6] = [
         (regex(r"---\s*([^\s\d]+)"), &identifier),
-        (regex(r"-|\*"), &list_item),
-        (regex(r#""(.*)""#), &path),
-        (regex(r"([^\]\n]*)\n?"), &text),
+        (
+            regex(r"(-|\*|#[.)]|\d{1,9}[.)]|[ivxlcdm]+\)|[IVXLCDM]+\)|[a-zA-Z]\))[^\S\n]"),
+            &list_item,
+        ),
+        (regex(r#"(?m)\[((?:\\.|[^\]\n])*)\][^\S\n]*"((?:\\.|[^"\n])*)"[^\S\n]*$"#), &image),
+        (regex(r#"(?m)"((?:\\.|[^"\n])*)"[^\S\n]*$"#), &path),
+        (regex(r"(?m)\+[^\S\n]*$\n?"), &paragraph_break),
+        (regex(r"([^\n]*)\n?"), &text),
     ];
 }
 
 fn text(_: usize, capture: Captures) -> Token {
-    Token::Text(capture.get(1).unwrap().as_str())
+    Token::Text(inline::split(capture.get(1).unwrap().as_str()))
+}
+
+fn paragraph_break(_: usize, _: Captures) -> Token {
+    Token::Text(vec![inline::PARAGRAPH_BREAK])
 }
 
 fn path(_: usize, capture: Captures) -> Token {
-    Token::Path(capture.get(1).unwrap().as_str().as_ref())
+    Token::Path(unescape_path(capture.get(1).unwrap().as_str()))
+}
+
+fn image(_: usize, capture: Captures) -> Token {
+    Token::Image(
+        inline::unescape(capture.get(1).unwrap().as_str()),
+        unescape_path(capture.get(2).unwrap().as_str()),
+    )
+}
+
+fn unescape_path(path: &str) -> Cow<'_, Path> {
+    match inline::unescape(path) {
+        Cow::Borrowed(p) => Cow::Borrowed(p.as_ref()),
+        Cow::Owned(p) => Cow::Owned(p.into()),
+    }
 }
 
 fn identifier(_: usize, capture: Captures) -> Token {
     Token::Identifier(capture.get(1).unwrap().as_str())
 }
 
-fn list_item(ident: usize, _: Captures) -> Token {
-    Token::ListPre(ident as u8)
+fn list_item(ident: usize, capture: Captures) -> Token {
+    Token::ListPre(ident, list::marker(capture.get(1).unwrap().as_str()))
 }
@@ -8,6 +8,8 @@ pub enum PdfError {
     File(io::Error),
     Image(image::ImageError),
     Pdf(printpdf::Error),
+    /// the saved document couldn't be changed afterwards
+    Rewrite(lopdf::Error),
 }
 
 impl std::error::Error for PdfError {}
@@ -22,6 +24,7 @@ impl fmt::Display for PdfError {
             File(e) => write!(f, "Couldn't read file due to {}", e),
             Image(e) => write!(f, "Couldn't load image due to {}", e),
             Pdf(e) => write!(f, "An pdf error occured: {}", e),
+            Rewrite(e) => write!(f, "Couldn't complete the saved pdf due to {}", e),
         }
     }
 }
@@ -43,3 +46,9 @@ impl From<printpdf::Error> for PdfError {
         Self::Pdf(e)
     }
 }
+
+impl From<lopdf::Error> for PdfError {
+    fn from(e: lopdf::Error) -> Self {
+        Self::Rewrite(e)
+    }
+}
@@ -1,15 +1,18 @@
 use crate::config;
-use arrayvec::ArrayVec;
 use printpdf::{image, Mm, Pt};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::io;
-use util::{LineData, PositionArgs, RtFont};
+use std::path::PathBuf;
+use unicode_bidi::ParagraphBidiInfo;
+use util::{LineData, LoadedRun, Opacity, PositionArgs, ShapingFont, Word, SOFT_HYPHEN};
 
 mod error;
+mod subset;
 mod util;
 
 pub use error::PdfError;
-pub use util::Size;
+pub use util::{hyphenate, Size};
 
 /// a rectangle inside the pdf document
 /// with a bottom-left origin
@@ -17,10 +20,19 @@ pub use util::Size;
 pub struct PdfRect(config::Rectangle<Pt>);
 
 impl PdfRect {
+    /// a rectangle with its origin at the bottom-left
+    pub fn new(orig: config::Point<Pt>, size: config::Point<Pt>) -> Self {
+        Self(config::Rectangle { orig, size })
+    }
+
     pub fn origin(&self) -> &config::Point<Pt> {
         &self.0.orig
     }
 
+    pub fn size(&self) -> &config::Point<Pt> {
+        &self.0.size
+    }
+
     /// creates an pdf rectangle from a "scalor" rectangle
     fn from(r: config::Rectangle<f64>, size: (Pt, Pt)) -> Self {
         let config::Rectangle {
@@ -67,20 +79,72 @@ pub struct TextArgs<'a> {
     pub area: PdfRect,
     pub font_size: f64,
     pub font: &'a str,
+    /// the variant of the font, runs start with it too
+    pub variant: config::FontVariant,
+    /// the color of text without a color of its own,
+    /// if none the current one is used
+    pub color: Option<config::Color>,
+    /// extra space after every glyph, in multiples of the font size
+    pub letter_spacing: f64,
     pub orientation: &'a config::Orientation,
+    /// factor for the height of the lines
+    pub line_spacing: f64,
+    /// space between paragraphs in multiples of the font size
+    pub paragraph_spacing: f64,
+}
+
+/// a piece of text drawn with the same font and color
+pub struct TextRun<'a> {
+    pub text: &'a str,
+    /// the font family, css generic names like
+    /// "monospace" or "serif" are supported too
+    pub font: &'a str,
+    pub variant: config::FontVariant,
+    /// the fill color, if none the current one is used
+    pub color: Option<config::Color>,
+    /// extra space after every glyph, in multiples of the font size
+    pub letter_spacing: f64,
+}
+
+impl<'a> TextRun<'a> {
+    /// a run in the variant of the font without a color or letter spacing
+    pub fn plain(text: &'a str, font: &'a str, variant: config::FontVariant) -> Self {
+        Self {
+            text,
+            font,
+            variant,
+            color: None,
+            letter_spacing: 0.0,
+        }
+    }
 }
 
 /// the pdf document itself
 pub struct Document {
     /// a map to the index of a font
-    /// fontname -> index
-    font_map: HashMap<String, usize>,
+    /// (fontname, variant) -> index
+    font_map: HashMap<(String, config::FontVariant), usize>,
+    /// the index of every face by its file and index inside the file,
+    /// variants resolving to the same face share it
+    face_map: HashMap<(PathBuf, u32), usize>,
     /// all fonts loaded as the printpdf format
     pdf_fonts: Vec<printpdf::IndirectFontRef>,
-    /// all fonts loaded as the rusttype format
-    rt_fonts: Vec<RtFont<'static>>,
+    /// all fonts loaded for shaping the text
+    shaping_fonts: Vec<ShapingFont<'static>>,
     /// fontconfig for finding the font paths
     font_config: fontdb::Database,
+    /// the fonts given by the style, they're preferred to the system fonts
+    style_fonts: fontdb::Database,
+    /// the font files and directories already added to the style fonts
+    font_paths: HashSet<PathBuf>,
+    /// fonts for chars the font of a run can't render
+    fallback: Vec<String>,
+    /// fallback fonts which couldn't be loaded
+    missing_fonts: HashSet<String>,
+    /// chars no font can render, which were already warned about
+    missing_chars: HashSet<char>,
+    /// the opacity of every graphics state added to a page, per page
+    opacities: RefCell<Vec<Vec<Opacity>>>,
 
     /// the printpdf document
     inner_doc: printpdf::PdfDocumentReference,
@@ -97,21 +161,30 @@ impl Document {
     pub fn new<S: Into<String>>(
         name: S,
         size: Size,
-        drawing_area: config::Rectangle<f64>,
+        drawing_area: config::Rectangle<config::Length>,
         dpi: u16,
+        fallback: Vec<String>,
     ) -> Result<Self> {
         let size = size.to_mm(dpi);
         let pt_size = (size.0.into(), size.1.into());
         let mut font_config = fontdb::Database::new();
         font_config.load_system_fonts();
+        set_generic_families(&mut font_config);
 
         Ok(Self {
             size,
-            drawing_area: PdfRect::from(drawing_area, pt_size),
+            drawing_area: PdfRect::from(util::scale_rect(drawing_area, pt_size, dpi), pt_size),
             font_map: Default::default(),
+            face_map: Default::default(),
             pdf_fonts: vec![],
-            rt_fonts: vec![],
+            shaping_fonts: vec![],
             font_config,
+            style_fonts: fontdb::Database::new(),
+            font_paths: Default::default(),
+            fallback,
+            missing_fonts: Default::default(),
+            missing_chars: Default::default(),
+            opacities: Default::default(),
             inner_doc: printpdf::PdfDocument::empty(name),
             dpi,
         })
@@ -120,9 +193,31 @@ impl Document {
     /// save the document to something implementing Write,
     /// please don't use BufWriter here, because the Writer
     /// will be wrapped into a BufWriter by this method.
-    pub fn save<W: io::Write>(self, to: W) -> Result<(), printpdf::Error> {
+    pub fn save<W: io::Write>(self, to: W) -> Result<()> {
+        // only the glyphs used on the pages are embedded, printpdf
+        // names the fonts in the order they're added like the references
+        let mut unmapped = vec![];
+        for (font, pdf_font) in self.shaping_fonts.iter().zip(self.pdf_fonts.iter()) {
+            let (data, glyphs) = font.subset();
+            unmapped.push(glyphs.unmapped());
+            let added = self.inner_doc.add_external_font_data(data, glyphs)?;
+            debug_assert_eq!(&added, pdf_font);
+        }
+
+        let opacities = self.opacities.into_inner();
         let mut buf_writer = io::BufWriter::new(to);
-        self.inner_doc.save(&mut buf_writer)
+        if opacities.iter().all(Vec::is_empty) && unmapped.iter().all(Vec::is_empty) {
+            return Ok(self.inner_doc.save(&mut buf_writer)?);
+        }
+
+        // printpdf can't write the opacity and the text of ligatures,
+        // they're added to the saved document
+        let mut pdf = vec![];
+        self.inner_doc.save(&mut io::BufWriter::new(&mut pdf))?;
+        let mut doc = lopdf::Document::load_mem(&pdf)?;
+        util::set_opacities(&mut doc, &opacities)?;
+        subset::map_glyphs(&mut doc, &unmapped)?;
+        Ok(doc.save_to(&mut buf_writer)?)
     }
 
     /// add a new page to the document, all future operation will be done
@@ -131,6 +226,7 @@ impl Document {
         let (page, layer) = self.inner_doc.add_page(self.size.0, self.size.1, name);
         let page = self.inner_doc.get_page(page);
         let layer = page.get_layer(layer);
+        self.opacities.get_mut().push(vec![]);
 
         let page = Page {
             doc: self,
@@ -144,98 +240,273 @@ impl Document {
         page
     }
 
+    /// adds the font files and the fonts inside the directories, which
+    /// aren't added yet. They're used instead of system fonts of the same family
+    pub fn add_fonts(&mut self, paths: &[PathBuf]) {
+        for path in paths.iter() {
+            if !self.font_paths.insert(path.clone()) {
+                continue;
+            }
+
+            if path.is_dir() {
+                self.style_fonts.load_fonts_dir(path);
+            } else if let Err(e) = self.style_fonts.load_font_file(path) {
+                eprintln!(
+                    "warning: the fonts of `{}` can't be used: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
     /// gets an PdfRectangle inside the drawing bounds with an "scalor"
-    /// rectangle
-    pub fn scale_pdf_rect(&self, area: config::Rectangle<f64>) -> PdfRect {
+    /// rectangle, absolute lengths are measured from its top-left
+    pub fn scale_pdf_rect(&self, area: config::Rectangle<config::Length>) -> PdfRect {
         let draw_area_size = self.drawing_area.0.size.into();
+        let area = util::scale_rect(area, draw_area_size, self.dpi);
         let mut tmp = PdfRect::from(area, draw_area_size);
         tmp.0.orig += self.drawing_area.0.orig;
         tmp
     }
 
-    pub fn get_width(&mut self, text: &str, font_size: f64, font_name: &str) -> Result<Pt> {
-        self.maybe_load_font(font_name)?;
-        let (_, font) = self.fonts(font_name);
+    pub fn get_width(
+        &mut self,
+        text: &str,
+        font_size: f64,
+        font_name: &str,
+        variant: config::FontVariant,
+    ) -> Result<Pt> {
+        self.maybe_load_font(font_name, variant)?;
+        let (_, font) = self.fonts(font_name, variant);
+
+        Ok(Pt(font.text_width(font_size as f32, text) as f64))
+    }
 
-        Ok(Pt(
-            font.text_width(font_size as f32, text.chars()).sum::<f32>() as f64,
-        ))
+    /// the height of a line of text in the font
+    pub fn line_height(
+        &mut self,
+        font_name: &str,
+        variant: config::FontVariant,
+        font_size: f64,
+    ) -> Result<Pt> {
+        self.maybe_load_font(font_name, variant)?;
+        let (_, font) = self.fonts(font_name, variant);
+
+        Ok(font.line_height * font_size)
     }
 
-    pub fn set_lower_left(&self, rect: &mut PdfRect, to: config::Point<Pt>) -> bool {
-        if self.drawing_area.0.is_inside_inclusive(to) {
-            rect.0.size += rect.0.orig - to;
-            rect.0.orig = to;
-            true
-        } else {
-            false
+    /// loads the font of the args and every font used by the runs,
+    /// the fallback fonts are loaded if a run has chars its font can't render
+    fn load_fonts(&mut self, args: &TextArgs<'_>, runs: &[TextRun<'_>]) -> Result<()> {
+        self.maybe_load_font(args.font, args.variant)?;
+        for run in runs.iter() {
+            self.maybe_load_font(run.font, run.variant)?;
+
+            let (_, font) = self.fonts(run.font, run.variant);
+            let mut chars = run.text.chars().filter(|c| needs_glyph(*c));
+            if chars.all(|c| font.has_glyph(c)) {
+                continue;
+            }
+
+            for name in self.fallback.clone() {
+                if self.missing_fonts.contains(&name) {
+                    continue;
+                }
+                if let Err(e) = self.maybe_load_font(&name, run.variant) {
+                    eprintln!("warning: the fallback font `{}` can't be used: {}", name, e);
+                    self.missing_fonts.insert(name);
+                }
+            }
+
+            let fonts = self.candidates(run);
+            let missing: Vec<char> = run
+                .text
+                .chars()
+                .filter(|c| needs_glyph(*c) && !fonts.iter().any(|(_, f)| f.has_glyph(*c)))
+                .collect();
+            let new: String = missing
+                .into_iter()
+                .filter(|c| self.missing_chars.insert(*c))
+                .collect();
+            if !new.is_empty() {
+                eprintln!("warning: no font can render the chars `{}`", new);
+            }
         }
+        Ok(())
     }
 
-    pub fn move_upper_right(&self, rect: &mut PdfRect, to_move: config::Point<Pt>) {
-        if self
-            .drawing_area
-            .0
-            .is_inside_inclusive(rect.0.size + to_move)
-        {
-            rect.0.size += to_move;
+    /// the font of the run followed by the loaded fallback fonts
+    fn candidates(
+        &self,
+        run: &TextRun<'_>,
+    ) -> Vec<(&printpdf::IndirectFontRef, &ShapingFont<'static>)> {
+        let fallback = self.fallback.iter().filter_map(|name| {
+            let index = *self.font_map.get(&(name.clone(), run.variant))?;
+            Some((&self.pdf_fonts[index], &self.shaping_fonts[index]))
+        });
+
+        std::iter::once(self.fonts(run.font, run.variant))
+            .chain(fallback)
+            .collect()
+    }
+
+    /// joins the text of the runs and remembers where each of them is,
+    /// the runs are split where a fallback font is needed.
+    /// The fonts need to be loaded already
+    fn loaded_runs(&self, runs: &[TextRun<'_>]) -> (String, Vec<LoadedRun<'_>>) {
+        let text: String = runs.iter().map(|r| r.text).collect();
+        let mut loaded = vec![];
+        let mut offset = 0;
+
+        for r in runs.iter() {
+            let fonts = self.candidates(r);
+            let mut push = |range: std::ops::Range<usize>, font: usize| {
+                let (pdf_font, font) = fonts[font];
+                loaded.push(LoadedRun {
+                    range,
+                    pdf_font,
+                    font,
+                    color: r.color,
+                    letter_spacing: r.letter_spacing as f32,
+                })
+            };
+
+            // the font of the run is preferred, else the font used
+            // for the last char is kept if it can render the char
+            let mut current = 0;
+            let mut start = offset;
+            for (i, c) in r.text.char_indices() {
+                let has_glyph = |f: usize| fonts[f].1.has_glyph(c);
+                let font = if !needs_glyph(c) || has_glyph(current) && !has_glyph(0) {
+                    current
+                } else {
+                    (0..fonts.len()).find(|f| has_glyph(*f)).unwrap_or(current)
+                };
+
+                if font != current {
+                    if offset + i > start {
+                        push(start..offset + i, current);
+                    }
+                    start = offset + i;
+                    current = font;
+                }
+            }
+
+            offset += r.text.len();
+            push(start..offset, current);
         }
+
+        (text, loaded)
     }
 
-    /// get the references to the font correspoding to the name
-    fn fonts(&self, name: &str) -> (&printpdf::IndirectFontRef, &RtFont<'static>) {
-        let index = *self.font_map.get(name).unwrap_or(&0);
-        (&self.pdf_fonts[index], &self.rt_fonts[index])
+    /// get the references to the font correspoding to the name,
+    /// the font needs to be loaded already
+    fn fonts(
+        &self,
+        name: &str,
+        variant: config::FontVariant,
+    ) -> (&printpdf::IndirectFontRef, &ShapingFont<'static>) {
+        let index = *self
+            .font_map
+            .get(&(String::from(name), variant))
+            .expect("fonts are loaded before they're used");
+        (&self.pdf_fonts[index], &self.shaping_fonts[index])
     }
 
     /// load a font if it's not already loaded
-    fn maybe_load_font(&mut self, name: &str) -> Result<()> {
+    fn maybe_load_font(&mut self, name: &str, variant: config::FontVariant) -> Result<()> {
         let font_not_found = || PdfError::FontNotFound(String::from(name));
         let font_not_loaded = || PdfError::FontNotLoaded(String::from(name));
+        let key = (String::from(name), variant);
         // it is already loaded
-        if self.font_map.contains_key(name) {
+        if self.font_map.contains_key(&key) {
             return Ok(());
         }
 
-        use fontdb::{Query, Source};
+        use fontdb::{Family, Query, Source};
+
+        let family = match name {
+            "serif" => Family::Serif,
+            "sans-serif" => Family::SansSerif,
+            "monospace" => Family::Monospace,
+            "cursive" => Family::Cursive,
+            "fantasy" => Family::Fantasy,
+            _ => Family::Name(name),
+        };
 
         let query = Query {
-            families: &[fontdb::Family::Name(name)],
-            weight: fontdb::Weight::NORMAL,
-            stretch: fontdb::Stretch::Normal,
-            style: fontdb::Style::Normal,
+            families: &[family],
+            weight: variant.weight,
+            stretch: variant.stretch,
+            style: variant.style,
         };
 
-        // get the font data
-        let data = {
-            let id = self.font_config.query(&query).ok_or_else(font_not_found)?;
-
-            self.font_config
-                .face_source(id)
-                .map(|d| match d.0.as_ref() {
-                    Source::File(p) => Some(std::fs::read(p).ok()?),
-                    _ => None,
-                })
-                .flatten()
-                .ok_or_else(font_not_found)?
+        // the fonts of the style take precedence over the system fonts
+        let face = [&self.style_fonts, &self.font_config]
+            .iter()
+            .find_map(|db| db.face_source(db.query(&query)?))
+            .ok_or_else(font_not_found)?;
+        let face = match face.0.as_ref() {
+            Source::File(p) => (p.clone(), face.1),
+            _ => return Err(font_not_found()),
         };
 
-        // read the font with printpdf and rusttype
-        let (pdf_font, rt_font) = (
-            self.inner_doc.add_external_font(&data[..])?,
-            rusttype::Font::try_from_vec(data).ok_or_else(font_not_loaded)?,
-        );
+        // the closest face was already loaded for another variant
+        if let Some(index) = self.face_map.get(&face) {
+            self.font_map.insert(key, *index);
+            return Ok(());
+        }
+
+        // get the font data
+        let data = std::fs::read(&face.0).map_err(|_| font_not_found())?;
+
+        // the shaper borrows the data, the fonts
+        // are needed until the document is saved anyway
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+
+        // the font is only added to the pdf when it's saved,
+        // until then it's referenced by the name printpdf will give it
+        let index = self.shaping_fonts.len();
+        let font = ShapingFont::from_data(data, face.1).ok_or_else(font_not_loaded)?;
+        let pdf_font = printpdf::IndirectFontRef::new(format!("F{}", index));
 
         // add the fonts to the map and lists
-        self.rt_fonts.push(RtFont::from_rt(rt_font));
+        self.shaping_fonts.push(font);
         self.pdf_fonts.push(pdf_font);
-        let index = self.rt_fonts.len() - 1;
-        self.font_map.insert(String::from(name), index);
+        self.font_map.insert(key, index);
+        self.face_map.insert(face, index);
 
         Ok(())
     }
 }
 
+/// lets fontconfig decide which fonts are used for generic
+/// families like "monospace", fontdb doesn't know them by itself
+fn set_generic_families(db: &mut fontdb::Database) {
+    let fc = match fontconfig::Fontconfig::new() {
+        Some(fc) => fc,
+        None => return,
+    };
+    let find = |family| fc.find(family, None).map(|f| f.name);
+
+    if let Some(f) = find("serif") {
+        db.set_serif_family(f);
+    }
+    if let Some(f) = find("sans-serif") {
+        db.set_sans_serif_family(f);
+    }
+    if let Some(f) = find("monospace") {
+        db.set_monospace_family(f);
+    }
+    if let Some(f) = find("cursive") {
+        db.set_cursive_family(f);
+    }
+    if let Some(f) = find("fantasy") {
+        db.set_fantasy_family(f);
+    }
+}
+
 /// a single page inside the pdf document
 /// used to drawing rectangles/text to this
 /// page
@@ -246,18 +517,12 @@ pub struct Page<'a> {
 }
 
 impl<'a> Page<'a> {
-    /// create a new layer, all future operation will be done
-    /// on it until a new layer needs/is created
-    pub fn new_layer<S: Into<String>>(&mut self, name: S) {
-        self.layer = self.page.add_layer(name);
-    }
-
-    const DBG_COLOR: printpdf::Color = printpdf::Color::Rgb(printpdf::Rgb {
+    const DBG_COLOR: config::Color = config::Color {
         r: 1.0,
         g: 0.0,
         b: 1.0,
-        icc_profile: None,
-    });
+        a: 1.0,
+    };
 
     pub fn draw_image<P: AsRef<std::path::Path>>(&self, path: P, area: &PdfRect) -> Result<()> {
         let pos = area.0.orig.map(|pt| Some(Mm::from(pt)));
@@ -286,10 +551,19 @@ impl<'a> Page<'a> {
     pub fn draw_rect(
         &self,
         rect: &PdfRect,
-        fill_color: Option<printpdf::Color>,
-        stroke_color: Option<printpdf::Color>,
+        fill_color: Option<config::Color>,
+        stroke_color: Option<config::Color>,
     ) {
         let layer = &self.layer;
+        // the opacity shouldn't change what's drawn afterwards
+        let translucent = fill_color
+            .iter()
+            .chain(stroke_color.iter())
+            .any(|c| !c.is_opaque());
+        if translucent {
+            layer.save_graphics_state();
+        }
+
         let line = printpdf::Line {
             points: rect.to_points(),
             is_closed: true,
@@ -299,129 +573,541 @@ impl<'a> Page<'a> {
         };
 
         // set the color
-        if let Some(c) = fill_color {
-            layer.set_fill_color(c);
+        self.set_colors(fill_color, stroke_color);
+
+        // and draw it
+        layer.add_shape(line);
+        if translucent {
+            layer.restore_graphics_state();
         }
-        if let Some(c) = stroke_color {
-            layer.set_outline_color(c);
+    }
+
+    /// sets the fill and stroke color of the layer, the opacity of
+    /// translucent colors is set by adding a graphics state
+    fn set_colors(&self, fill: Option<config::Color>, stroke: Option<config::Color>) {
+        if let Some(c) = fill {
+            self.layer.set_fill_color(c.into());
+        }
+        if let Some(c) = stroke {
+            self.layer.set_outline_color(c.into());
         }
 
-        // and draw it
-        layer.add_shape(line)
+        let alpha = |c: Option<config::Color>| c.map_or(1.0, |c| c.a);
+        let opacity = Opacity {
+            fill: alpha(fill),
+            stroke: alpha(stroke),
+        };
+        if opacity.fill < 1.0 || opacity.stroke < 1.0 {
+            use printpdf::{BlendMode, SeperableBlendMode};
+            self.layer
+                .set_blend_mode(BlendMode::Seperable(SeperableBlendMode::Normal));
+            // PANICS: every page adds its list of opacities
+            self.doc
+                .opacities
+                .borrow_mut()
+                .last_mut()
+                .unwrap()
+                .push(opacity);
+        }
+    }
+
+    /// everything drawn on this layer is cut off outside
+    /// of the area, until the clipping is ended
+    pub fn start_clip(&self, area: &PdfRect) {
+        self.layer.save_graphics_state();
+        self.layer.add_shape(printpdf::Line {
+            points: area.to_points(),
+            is_closed: true,
+            has_fill: false,
+            has_stroke: false,
+            is_clipping_path: true,
+        });
+    }
+
+    /// ends the clipping started with `start_clip`
+    pub fn end_clip(&self) {
+        self.layer.restore_graphics_state();
+    }
+
+    /// draws a bullet in front of the first line of text
+    /// drawn with the same args, glyphs use the font of the args.
+    /// Right oriented bullets are drawn at the right side of the area
+    pub fn draw_bullet(
+        &mut self,
+        args: &TextArgs<'_>,
+        bullet: &config::Bullet,
+        color: Option<config::Color>,
+    ) -> Result<()> {
+        use config::Bullet;
+        let color = color.or(args.color);
+
+        // shapes and images are centered around the middle of lower case letters
+        let line_height = self
+            .doc
+            .line_height(args.font, args.variant, args.font_size)?
+            * args.line_spacing;
+        let area = &args.area.0;
+        let right = args.orientation.horizontal == config::HorOrientation::Right;
+        let shape = |size: f64| {
+            let center = config::Point {
+                x: if right {
+                    area.orig.x + area.size.x - Pt(size / 2.0)
+                } else {
+                    area.orig.x + Pt(size / 2.0)
+                },
+                y: area.orig.y + area.size.y - line_height + Pt(args.font_size * 0.3),
+            };
+            (center, Pt(size))
+        };
+
+        match bullet {
+            Bullet::Glyph(text) => {
+                let run = TextRun {
+                    color,
+                    ..TextRun::plain(text, args.font, args.variant)
+                };
+                self.draw_text(args, &[run])?;
+            }
+            Bullet::Square | Bullet::Circle => {
+                let (center, size) = shape(args.font_size * 0.35);
+                let points = if bullet == &Bullet::Square {
+                    printpdf::utils::calculate_points_for_rect(size, size, center.x, center.y)
+                } else {
+                    printpdf::utils::calculate_points_for_circle(size / 2.0, center.x, center.y)
+                };
+
+                self.layer.save_graphics_state();
+                self.set_colors(color, None);
+                self.layer.add_shape(printpdf::Line {
+                    points,
+                    is_closed: true,
+                    has_fill: true,
+                    has_stroke: false,
+                    is_clipping_path: false,
+                });
+                self.layer.restore_graphics_state();
+            }
+            Bullet::Image(path) => {
+                let (center, size) = shape(args.font_size * 0.7);
+                let area = PdfRect(config::Rectangle {
+                    orig: center - config::Point { x: size, y: size }.map(|s| s / 2.0),
+                    size: config::Point { x: size, y: size },
+                });
+                self.draw_image(path, &area)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the size the runs would take up if they were drawn
+    /// with the args, (widest line, height of all lines)
+    pub fn measure_text(
+        &mut self,
+        args: &TextArgs<'_>,
+        runs: &[TextRun<'_>],
+    ) -> Result<config::Point<Pt>> {
+        self.doc.load_fonts(args, runs)?;
+        let (_, font) = self.doc.fonts(args.font, args.variant);
+        let (text, loaded) = self.doc.loaded_runs(runs);
+
+        let lines: Vec<_> =
+            Self::get_lines(&loaded, &text, args.font_size as f32, args.area.0.size.x.0).collect();
+        let width = lines.iter().map(|l| l.width).fold(0.0, f32::max);
+        let pos_args = PositionArgs::new(args, &lines, font);
+
+        Ok(config::Point {
+            x: Pt(width as f64),
+            y: Pt(pos_args.height()),
+        })
+    }
+
+    /// the byte index inside the joined text of the runs up to which
+    /// the lines fit into the area, the first line always fits
+    pub fn fitting_text(&mut self, args: &TextArgs<'_>, runs: &[TextRun<'_>]) -> Result<usize> {
+        self.doc.load_fonts(args, runs)?;
+        let (_, font) = self.doc.fonts(args.font, args.variant);
+        let (text, loaded) = self.doc.loaded_runs(runs);
+
+        let lines: Vec<_> =
+            Self::get_lines(&loaded, &text, args.font_size as f32, args.area.0.size.x.0).collect();
+        let pos_args = PositionArgs::new(args, &lines, font);
+
+        let available = args.area.0.size.y.0;
+        let fitting = (1..=lines.len())
+            .take_while(|n| *n == 1 || pos_args.height_of(*n) <= available)
+            .last()
+            .unwrap_or(0);
+
+        Ok(lines.get(fitting).map_or(text.len(), |l| l.start_index))
     }
 
-    /// draw the text with the text args.
+    /// draw the runs of text with the text args.
     /// if the text exceeds the horizontal boundaries, it will be word wrapped
-    pub fn draw_text(&mut self, args: &TextArgs<'_>, text: &str) -> Result<Pt> {
+    pub fn draw_text(&mut self, args: &TextArgs<'_>, runs: &[TextRun<'_>]) -> Result<Pt> {
         // draw the box outlines in debug mode
         #[cfg(debug_assertions)]
         self.draw_rect(&args.area, None, Some(Self::DBG_COLOR));
 
         // get the fonts
-        self.doc.maybe_load_font(args.font)?;
-        let (pdf_font, rt_font) = self.doc.fonts(args.font);
+        self.doc.load_fonts(args, runs)?;
+        let (_, font) = self.doc.fonts(args.font, args.variant);
+        let (text, loaded) = self.doc.loaded_runs(runs);
 
         // reassign for readability
         let width = args.area.0.size.x.0;
         let font_size = args.font_size;
-        let whitespace_width = rt_font.get_width(font_size as f32, ' ');
         let layer = &self.layer;
 
-        // PANICS: content with more than 64 lines should be a sin
-        // TODO: maybe use Vec for better memory usage
-        let beginnings: ArrayVec<_, 64> =
-            Self::get_lines(rt_font, text, font_size as f32, width, whitespace_width).collect();
-        let pos_args = PositionArgs::new(args, &beginnings, rt_font);
-
-        let mut i = 0;
-        let mut start = 0; // start at index 0, duh
-
-        for line in beginnings.iter() {
-            // prepare line
-            let end = line.end_index;
-            let pos = pos_args.get_position(i);
+        let lines: Vec<_> = Self::get_lines(&loaded, &text, font_size as f32, width).collect();
+        let pos_args = PositionArgs::new(args, &lines, font);
+
+        // the levels of the text inside every paragraph for the bidi reordering
+        let paragraphs: Vec<_> = text
+            .split('\n')
+            .map(|p| {
+                (
+                    util::get_index_of(p, &text),
+                    ParagraphBidiInfo::new(p, None),
+                )
+            })
+            .collect();
+
+        for (i, line) in lines.iter().enumerate() {
+            let line_text = &text[line.start_index..line.end_index];
+            let gaps = line_text.chars().filter(|c| is_breaking_space(*c)).count();
+            let mut pos = pos_args.get_position(i, gaps);
+            let extra = pos_args.justify_space(i, gaps);
+
+            // the parts of the runs inside the line in visual order,
+            // together with their direction
+            let (offset, bidi) = &paragraphs[line.paragraph];
+            let (levels, bidi_runs) =
+                bidi.visual_runs(line.start_index - offset..line.end_index - offset);
+            let mut parts = vec![];
+            for range in bidi_runs {
+                let rtl = levels[range.start].is_rtl();
+                let (start, end) = (range.start + offset, range.end + offset);
+
+                let runs = loaded.iter().filter_map(|run| {
+                    let start = run.range.start.max(start);
+                    let end = run.range.end.min(end);
+                    (start < end).then(|| (run, &text[start..end], rtl))
+                });
+                let index = parts.len();
+                parts.extend(runs);
+                if rtl {
+                    parts[index..].reverse();
+                }
+            }
 
-            //dbg!(&text[start..end], start, end, line.width, pos);
-            layer.use_text(&text[start..end], font_size, pos.x, pos.y, pdf_font);
+            // the line was broken at a soft hyphen, which
+            // is at the left side of right-to-left lines
+            let hyphen_run = loaded
+                .iter()
+                .find(|r| r.range.contains(&(line.end_index - 1)));
+            if let (true, Some(run)) = (line.hyphen, hyphen_run) {
+                let hyphen = (run, "-", false);
+                if line.rtl {
+                    parts.insert(0, hyphen);
+                } else {
+                    parts.push(hyphen);
+                }
+            }
 
-            // the end is always at a whitespace
-            // except for the last
-            start = end + 1;
-            i += 1; // increase the index
+            for (run, part, rtl) in parts {
+                if run.color.is_some() {
+                    layer.save_graphics_state();
+                    self.set_colors(run.color, None);
+                }
+
+                // justified lines are drawn word by word
+                let mut pieces: Vec<_> = if extra > 0.0 {
+                    part.split_inclusive(is_breaking_space).collect()
+                } else {
+                    vec![part]
+                };
+                if rtl {
+                    pieces.reverse();
+                }
+
+                for piece in pieces {
+                    // the whitespace is on the left side of right-to-left words
+                    let space: Mm = if piece.ends_with(is_breaking_space) {
+                        Pt(extra).into()
+                    } else {
+                        Mm(0.0)
+                    };
+
+                    if rtl {
+                        pos.x += space;
+                    }
+                    write_glyphs(layer, run, piece, font_size, pos, rtl);
+                    pos.x += Pt(run.width(piece, font_size as f32) as f64).into();
+                    if !rtl {
+                        pos.x += space;
+                    }
+                }
+
+                if run.color.is_some() {
+                    layer.restore_graphics_state();
+                }
+            }
         }
 
-        Ok(Pt(i as f64) * pos_args.line_height)
+        Ok(Pt(pos_args.height()))
     }
 
     /// splits the text into lines which are
     /// inside the horizontal boundaries
     fn get_lines<'b>(
-        font: &'b RtFont<'b>,
+        runs: &'b [LoadedRun<'b>],
         text: &'b str,
         font_size: f32,
         width: f64,
-        whitespace_width: f32,
     ) -> impl Iterator<Item = LineData> + 'b {
         //eprintln!("max width of the line: {}", width);
+        let max_width = width as f32;
+
+        // the width of a part of the text, which can be made of multiple runs
+        let text_width = move |start: usize, end: usize| -> f32 {
+            runs.iter()
+                .filter(|r| r.range.start < end && start < r.range.end)
+                .map(|r| {
+                    let part = &text[r.range.start.max(start)..r.range.end.min(end)];
+                    r.width(part, font_size)
+                })
+                .sum()
+        };
 
-        // TODO: maybe support other chars
-        text.split_ascii_whitespace()
-            .map(move |word| {
-                Some((
-                    // the start index of the word
-                    util::get_index_of(word, text),
-                    // the width of the word
-                    font.text_width(font_size, word.chars()).sum(),
-                ))
+        // every line break inside the text starts a new paragraph
+        text.split('\n')
+            .enumerate()
+            .flat_map(move |(paragraph, part)| {
+                let rtl = is_rtl(part);
+                let part = part.trim_start();
+                let offset = util::get_index_of(part, text);
+
+                // the text between two break opportunities (UAX #14)
+                // is a word together with the whitespace behind it
+                unicode_linebreak::linebreaks(part)
+                    .scan(0, move |last, (end, _)| {
+                        let segment = (offset + *last, offset + end);
+                        *last = end;
+                        Some(segment)
+                    })
+                    .flat_map(move |(start, end)| {
+                        let word = text[start..end].trim_end_matches(is_breaking_space);
+                        let word_end = start + word.len();
+
+                        let hyphen = if word.ends_with(SOFT_HYPHEN) {
+                            runs.iter()
+                                .find(|r| r.range.contains(&(word_end - 1)))
+                                .map(|r| r.font.text_width(font_size, "-"))
+                        } else {
+                            None
+                        };
+
+                        let word = Word {
+                            start,
+                            end: word_end,
+                            width: text_width(start, word_end),
+                            space: text_width(word_end, end),
+                            hyphen,
+                        };
+                        break_word(word, max_width, text, text_width)
+                    })
+                    .map(Some)
+                    .chain(std::iter::once(None)) // marks the end of the paragraph
+                    .filter_map(is_line_end(max_width))
+                    .map(move |line| LineData {
+                        paragraph,
+                        rtl,
+                        ..line
+                    })
             })
-            .chain(std::iter::once(None)) // marks the end of the text
-            .filter_map(is_line_end(width as f32, whitespace_width, text.len()))
     }
 }
 
+/// writes the shaped glyphs of the text in the font of the run,
+/// printpdf moves every glyph by the width it knows for it,
+/// so the differences to the shaped positions are corrected
+fn write_glyphs(
+    layer: &printpdf::PdfLayerReference,
+    run: &LoadedRun<'_>,
+    text: &str,
+    font_size: f64,
+    pos: config::Point<Mm>,
+    rtl: bool,
+) {
+    let font = run.font;
+    // from font units to thousandths of the font size
+    let to_pdf = font.scale(1000.0) as f64;
+    // the letter spacing in font units
+    let spacing = run.letter_spacing as f64 / font.scale(1.0) as f64;
+
+    layer.begin_text_section();
+    layer.set_font(run.pdf_font, font_size);
+    layer.set_text_cursor(pos.x, pos.y);
+
+    // the shaped position and the one inside the pdf
+    let mut shaped = 0.0;
+    let mut pdf = 0.0;
+    let glyphs = font.shape(text, Some(rtl));
+    font.mark_used(text, &glyphs, rtl);
+    // glyphs moved up or down (ex. marks) are written with a text rise
+    for group in glyphs.chunk_by(|a, b| a.offset.1 == b.offset.1) {
+        let rise = group[0].offset.1;
+        if rise != 0 {
+            layer.set_line_offset(rise as f64 * font.scale(font_size as f32) as f64);
+        }
+
+        let positioned: Vec<_> = group
+            .iter()
+            .map(|g| {
+                let target = (shaped + g.offset.0 as f64) * to_pdf;
+                let adjust = (pdf - target).round();
+                pdf += font.pdf_width(g.id) - adjust;
+                shaped += g.advance as f64;
+                // marks don't move the next glyph, so they aren't spaced
+                if g.advance != 0 {
+                    shaped += spacing;
+                }
+                (adjust as i64, g.id)
+            })
+            .collect();
+        layer.write_positioned_codepoints(positioned);
+
+        if rise != 0 {
+            layer.set_line_offset(0.0);
+        }
+    }
+
+    layer.end_text_section();
+}
+
+/// if the text is written from right to left,
+/// it's decided by the first char with a strong direction
+pub fn is_rtl(text: &str) -> bool {
+    unicode_bidi::get_base_direction(text) == unicode_bidi::Direction::Rtl
+}
+
+/// if the char needs a glyph, whitespace and invisible chars like
+/// soft hyphens or variation selectors are drawn with the font around them
+fn needs_glyph(c: char) -> bool {
+    !(c.is_whitespace()
+        || c.is_control()
+        || c == SOFT_HYPHEN
+        || ('\u{200b}'..='\u{200f}').contains(&c)
+        || ('\u{fe00}'..='\u{fe0f}').contains(&c))
+}
+
+/// whitespace a line can be broken at,
+/// the no-break spaces keep the words around them together
+fn is_breaking_space(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+/// breaks a word which is wider than a line into pieces that fit on a line,
+/// only the last piece keeps the whitespace and hyphen behind the word
+fn break_word<F>(word: Word, max_width: f32, text: &str, text_width: F) -> Vec<Word>
+where
+    F: Fn(usize, usize) -> f32,
+{
+    if word.width <= max_width {
+        return vec![word];
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = word.start;
+    for (i, c) in text[word.start..word.end].char_indices() {
+        let idx = word.start + i;
+        // every piece has at least one char
+        if idx > start && text_width(start, idx + c.len_utf8()) > max_width {
+            pieces.push(Word {
+                start,
+                end: idx,
+                width: text_width(start, idx),
+                space: 0.0,
+                hyphen: None,
+            });
+            start = idx;
+        }
+    }
+
+    pieces.push(Word {
+        start,
+        width: text_width(start, word.end),
+        ..word
+    });
+    pieces
+}
+
 /// returns a function which determines,
 /// if a word exceeds the current line,
 /// if it does Some(LineData) will be returned
 /// else None.
-fn is_line_end(
-    max_width: f32,
-    whitespace_width: f32,
-    str_len: usize,
-) -> impl FnMut(Option<(usize, f32)>) -> Option<LineData> {
-    let mut p_sum = 0.0;
-
-    move |o| {
-        if let Some((i, w)) = o {
-            p_sum += w;
-
-            if p_sum > max_width {
-                let line_width = p_sum - w;
-                p_sum = w;
-
-                Some(LineData {
-                    // get the index of the whitespace before
-                    end_index: i - 1,
-                    // w overshoot
-                    width: line_width,
-                })
+fn is_line_end(max_width: f32) -> impl FnMut(Option<Word>) -> Option<LineData> {
+    // the first and last byte of the words on the current line
+    let mut line: Option<(usize, usize)> = None;
+    // width up to the end of the last word
+    let mut width = 0.0;
+    // the whitespace behind the last word
+    let mut space = 0.0;
+    // the hyphen needed if the line is broken behind the last word
+    let mut hyphen: Option<f32> = None;
+
+    move |o| match (o, line) {
+        (Some(w), Some((start, end))) => {
+            if width + space + w.width + w.hyphen.unwrap_or(0.0) > max_width {
+                line = Some((w.start, w.end));
+                let data = LineData {
+                    start_index: start,
+                    end_index: end,
+                    width: width + hyphen.unwrap_or(0.0),
+                    paragraph: 0,
+                    hyphen: hyphen.is_some(),
+                    rtl: false,
+                };
+                width = w.width;
+                space = w.space;
+                hyphen = w.hyphen;
+
+                Some(data)
             } else {
                 // TODO: add kerning between last and whitespace
-                // add the whitespace width if this word is still on the line
-                p_sum += whitespace_width;
+                line = Some((start, w.end));
+                width += space + w.width;
+                space = w.space;
+                hyphen = w.hyphen;
                 None
             }
-        } else {
-            Some(LineData {
-                end_index: str_len,
-                width: p_sum,
-            })
         }
+        // the first word is always on the line,
+        // even if it's too long
+        (Some(w), None) => {
+            line = Some((w.start, w.end));
+            width = w.width;
+            space = w.space;
+            hyphen = w.hyphen;
+            None
+        }
+        // the end of a paragraph is no soft hyphen break
+        (None, Some((start, end))) => Some(LineData {
+            start_index: start,
+            end_index: end,
+            width,
+            paragraph: 0,
+            hyphen: false,
+            rtl: false,
+        }),
+        (None, None) => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{Point, Rectangle};
+    use crate::config::{Length, Point, Rectangle};
     use printpdf::{Mm, Pt};
 
     fn equal_within_error(left: f64, right: f64) {
@@ -489,4 +1175,208 @@ mod tests {
             super::PdfRect(expected)
         );
     }
+
+    #[test]
+    fn rect_lengths_to_scale() {
+        let rect = Rectangle {
+            orig: Point {
+                x: Length::Relative(0.5),
+                y: Length::Pt(25.0),
+            },
+            // both are an inch, 72pt
+            size: Point {
+                x: Length::Mm(25.4),
+                y: Length::Px(96.0),
+            },
+        };
+        let scaled = super::util::scale_rect(rect, RECT_SIZE, DPI);
+
+        equal_within_error(scaled.orig.x, 0.5);
+        equal_within_error(scaled.orig.y, 0.25);
+        equal_within_error(scaled.size.x, 0.72);
+        equal_within_error(scaled.size.y, 0.72);
+    }
+
+    /// every char is one unit wide
+    fn char_width(text: &str) -> impl Fn(usize, usize) -> f32 + '_ {
+        move |start, end| text[start..end].chars().count() as f32
+    }
+
+    fn word(text: &str, start: usize, end: usize, space: f32) -> super::Word {
+        super::Word {
+            start,
+            end,
+            width: char_width(text)(start, end),
+            space,
+            hyphen: None,
+        }
+    }
+
+    #[test]
+    fn rtl_paragraphs_are_detected() {
+        assert!(super::is_rtl("שלום world"));
+        assert!(super::is_rtl("123 مرحبا"));
+        assert!(!super::is_rtl("hello שלום"));
+    }
+
+    #[test]
+    fn words_are_hyphenated() {
+        let text = "extensive text, \u{ad}kept";
+        assert_eq!(
+            super::hyphenate(text, hypher::Lang::English),
+            "ex\u{ad}ten\u{ad}sive text, \u{ad}kept"
+        );
+    }
+
+    #[test]
+    fn long_word_is_broken() {
+        let text = "abcdefghij";
+        let pieces = super::break_word(word(text, 0, 10, 1.0), 4.0, text, char_width(text));
+        let ranges: Vec<_> = pieces.iter().map(|w| (w.start, w.end)).collect();
+
+        assert_eq!(ranges, vec![(0, 4), (4, 8), (8, 10)]);
+        // only the last piece keeps the space behind the word
+        assert_eq!(pieces[0].space, 0.0);
+        assert_eq!(pieces[2].space, 1.0);
+    }
+
+    #[test]
+    fn invisible_chars_need_no_glyph() {
+        assert!(super::needs_glyph('a'));
+        assert!(super::needs_glyph('中'));
+        assert!(!super::needs_glyph(' '));
+        assert!(!super::needs_glyph('\u{ad}'));
+        assert!(!super::needs_glyph('\u{200d}'));
+        assert!(!super::needs_glyph('\u{fe0f}'));
+    }
+
+    #[test]
+    fn soft_hyphen_at_line_end() {
+        // "hy\u{ad}phen" with a soft hyphen, followed by "pen"
+        let text = "hy\u{ad}phen pen";
+        let hyphenated = super::Word {
+            hyphen: Some(1.0),
+            width: 2.0,
+            ..word(text, 0, 4, 0.0)
+        };
+        let words = vec![
+            Some(hyphenated),
+            Some(word(text, 4, 8, 1.0)),
+            Some(word(text, 9, 12, 0.0)),
+            None,
+        ];
+
+        let lines: Vec<_> = words
+            .into_iter()
+            .filter_map(super::is_line_end(4.0))
+            .map(|l| (l.start_index, l.end_index, l.hyphen))
+            .collect();
+
+        assert_eq!(lines, vec![(0, 4, true), (4, 8, false), (9, 12, false)]);
+    }
+
+    #[test]
+    fn rtl_justified_line_fills_area() {
+        let font = super::ShapingFont::from_data(TEST_FONT, 0).unwrap();
+        let orientation = crate::config::Orientation {
+            horizontal: crate::config::HorOrientation::Justify,
+            ..Default::default()
+        };
+        let args = super::TextArgs {
+            area: super::PdfRect(Rectangle {
+                orig: Point {
+                    x: Pt(10.0),
+                    y: Pt(20.0),
+                },
+                size: Point {
+                    x: Pt(100.0),
+                    y: Pt(50.0),
+                },
+            }),
+            font_size: 10.0,
+            font: "",
+            variant: Default::default(),
+            color: None,
+            letter_spacing: 0.0,
+            orientation: &orientation,
+            line_spacing: 1.0,
+            paragraph_spacing: 0.0,
+        };
+        let line = |start_index, width| super::LineData {
+            start_index,
+            end_index: start_index + 10,
+            width,
+            paragraph: 0,
+            hyphen: false,
+            rtl: true,
+        };
+        let lines = [line(0, 60.0), line(10, 30.0)];
+        let pos_args = super::PositionArgs::new(&args, &lines, &font);
+
+        // the stretched line spans the whole width
+        let x = |i, gaps| Pt::from(pos_args.get_position(i, gaps).x).0 - 10.0;
+        equal_within_error(x(0, 2), 0.0);
+        equal_within_error(60.0 + 2.0 * pos_args.justify_space(0, 2), 100.0);
+        // the last line of the paragraph starts on the right
+        equal_within_error(x(1, 2), 70.0);
+    }
+
+    /// a small font that comes with the tests,
+    /// so they don't depend on the installed fonts
+    const TEST_FONT: &[u8] = include_bytes!("../../../tests/fonts/SourceSansPro-Regular-Tiny.ttf");
+
+    #[test]
+    fn fonts_are_subset() {
+        let font = super::ShapingFont::from_data(TEST_FONT, 0).unwrap();
+        let glyphs = font.shape("Subset", None);
+        font.mark_used("Subset", &glyphs, false);
+
+        let (subset, used) = font.subset();
+        assert!(subset.len() < TEST_FONT.len());
+
+        // the glyph ids stay the same, only unused outlines are removed
+        let original = rustybuzz::Face::from_slice(TEST_FONT, 0).unwrap();
+        let subset = rustybuzz::Face::from_slice(&subset, 0).unwrap();
+        for g in glyphs.iter() {
+            let id = rustybuzz::ttf_parser::GlyphId(g.id);
+            assert!(used.glyphs.contains_key(&g.id));
+            assert_eq!(
+                subset.glyph_bounding_box(id),
+                original.glyph_bounding_box(id)
+            );
+        }
+        let unused = original.glyph_index('Z').unwrap();
+        assert!(original.glyph_bounding_box(unused).is_some());
+        assert!(subset.glyph_bounding_box(unused).is_none());
+    }
+
+    #[test]
+    fn glyphs_stand_for_their_cluster() {
+        let font = super::ShapingFont::from_data(TEST_FONT, 0).unwrap();
+        let glyph = |id, cluster| super::util::Glyph {
+            id,
+            advance: 0,
+            offset: (0, 0),
+            cluster,
+        };
+
+        // a ligature, a soft hyphen removed by the shaper
+        // and a char with a combining mark drawn as two glyphs
+        let text = "fi\u{ad}e\u{301}";
+        let glyphs = [glyph(1, 0), glyph(2, 4), glyph(3, 4)];
+        font.mark_used(text, &glyphs, false);
+        // right-to-left clusters start with their last glyph
+        font.mark_used("a\u{301}", &[glyph(5, 0), glyph(4, 0)], true);
+
+        let used = font.subset().1;
+        let text = |id| used.glyphs[&id].0.as_str();
+        assert_eq!(
+            [text(1), text(2), text(3), text(4), text(5)],
+            ["fi", "e", "\u{301}", "a", "\u{301}"]
+        );
+
+        let mut unmapped = used.unmapped();
+        unmapped.sort();
+        assert_eq!(unmapped, vec![(1, "fi".to_string())]);
+    }
 }
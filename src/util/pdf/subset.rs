@@ -0,0 +1,118 @@
+use printpdf::types::plugins::graphics::two_dimensional::{FontData, FontMetrics, GlyphMetrics};
+use std::collections::{HashMap, HashSet};
+
+/// the glyphs of a font which are written into the pdf,
+/// printpdf takes their widths and the chars they stand for from it
+#[derive(Debug, Clone)]
+pub struct UsedGlyphs {
+    pub metrics: FontMetrics,
+    /// glyph id -> (text, metrics)
+    pub glyphs: HashMap<u16, (String, GlyphMetrics)>,
+}
+
+impl UsedGlyphs {
+    /// the glyphs printpdf can't map to their text, because they stand for
+    /// several chars (ligatures), none or one outside of the BMP
+    pub fn unmapped(&self) -> Vec<(u16, String)> {
+        self.glyphs
+            .iter()
+            .filter(|(_, (text, _))| text.encode_utf16().count() != 1)
+            .map(|(id, (text, _))| (*id, text.clone()))
+            .collect()
+    }
+}
+
+impl FontData for UsedGlyphs {
+    fn font_metrics(&self) -> FontMetrics {
+        self.metrics
+    }
+
+    fn glyph_id(&self, c: char) -> Option<u16> {
+        self.glyphs
+            .iter()
+            .find(|(_, (text, _))| text.chars().eq(Some(c)))
+            .map(|(id, _)| *id)
+    }
+
+    fn glyph_ids(&self) -> HashMap<u16, char> {
+        // the unmapped glyphs are mapped to their text after saving
+        self.glyphs
+            .iter()
+            .map(|(id, (text, _))| (*id, text.chars().next().unwrap_or(' ')))
+            .collect()
+    }
+
+    fn glyph_metrics(&self, glyph_id: u16) -> Option<GlyphMetrics> {
+        self.glyphs.get(&glyph_id).map(|(_, metrics)| *metrics)
+    }
+}
+
+/// removes the outlines of all glyphs except the used ones from the font,
+/// the glyph ids stay the same. The whole font is kept if it can't be subset
+pub fn subset(data: &[u8], index: u32, glyphs: &UsedGlyphs) -> Vec<u8> {
+    // the missing glyph is always needed
+    let mut ids: Vec<u16> = glyphs.glyphs.keys().copied().chain(Some(0)).collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    subsetter::subset(data, index, subsetter::Profile::pdf(&ids)).unwrap_or_else(|e| {
+        eprintln!(
+            "warning: a font couldn't be subset, it's embedded as a whole: {}",
+            e
+        );
+        data.to_vec()
+    })
+}
+
+/// maps the unmapped glyphs to their text in the ToUnicode maps of the saved
+/// document, printpdf writes one line for every glyph into them.
+/// The fonts are given in the order printpdf names them F0, F1, ...
+pub fn map_glyphs(
+    doc: &mut lopdf::Document,
+    unmapped: &[Vec<(u16, String)>],
+) -> Result<(), lopdf::Error> {
+    let mut maps = HashSet::new();
+    for page in doc.get_pages().values() {
+        let resources = doc.get_dictionary(*page)?.get(b"Resources")?;
+        let resources = doc.dereference(resources)?.1.as_dict()?;
+        let fonts = match resources.get(b"Font") {
+            Ok(fonts) => doc.dereference(fonts)?.1.as_dict()?,
+            Err(_) => continue,
+        };
+
+        for (i, glyphs) in unmapped.iter().enumerate() {
+            let font = match fonts.get(format!("F{}", i).as_bytes()) {
+                Ok(font) if !glyphs.is_empty() => doc.dereference(font)?.1.as_dict()?,
+                _ => continue,
+            };
+            maps.insert((i, font.get(b"ToUnicode")?.as_reference()?));
+        }
+    }
+
+    for (i, id) in maps {
+        let stream = doc.get_object_mut(id)?.as_stream_mut()?;
+        // streams without a filter can't be decompressed
+        let cmap = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        let cmap: Vec<_> = String::from_utf8_lossy(&cmap)
+            .split('\n')
+            .map(|line| {
+                let glyph = unmapped[i]
+                    .iter()
+                    .find(|(id, _)| line.starts_with(&format!("<{:04x}> <", id)));
+                match glyph {
+                    Some((id, text)) => {
+                        let utf16: String =
+                            text.encode_utf16().map(|u| format!("{:04x}", u)).collect();
+                        format!("<{:04x}> <{}>", id, utf16)
+                    }
+                    None => line.to_string(),
+                }
+            })
+            .collect();
+        stream.set_plain_content(cmap.join("\n").into_bytes());
+    }
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
+use super::subset::UsedGlyphs;
 use crate::config;
+use printpdf::types::plugins::graphics::two_dimensional::{FontMetrics, GlyphMetrics};
 use printpdf::{Mm, Pt, Px};
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 impl From<config::Color> for printpdf::Color {
     fn from(c: config::Color) -> Self {
@@ -12,6 +16,47 @@ impl From<config::Color> for printpdf::Color {
     }
 }
 
+/// the fill and stroke opacity of a graphics state
+#[derive(Debug, Clone, Copy)]
+pub struct Opacity {
+    pub fill: f64,
+    pub stroke: f64,
+}
+
+/// printpdf can't set the opacity, so it's set afterwards on the graphics states
+/// of the saved document. printpdf names them GS0, GS1, ... for every page,
+/// the opacities are given in the same order
+pub fn set_opacities(
+    doc: &mut lopdf::Document,
+    opacities: &[Vec<Opacity>],
+) -> Result<(), lopdf::Error> {
+    let pages: Vec<_> = doc.get_pages().values().copied().collect();
+    for (page, opacities) in pages.into_iter().zip(opacities) {
+        if opacities.is_empty() {
+            continue;
+        }
+
+        let resources = doc
+            .get_dictionary(page)?
+            .get(b"Resources")?
+            .as_reference()?;
+        let states = doc
+            .get_object_mut(resources)?
+            .as_dict_mut()?
+            .get_mut(b"ExtGState")?
+            .as_dict_mut()?;
+        for (i, opacity) in opacities.iter().enumerate() {
+            let state = states
+                .get_mut(format!("GS{}", i).as_bytes())?
+                .as_dict_mut()?;
+            state.set("ca", opacity.fill);
+            state.set("CA", opacity.stroke);
+        }
+    }
+
+    Ok(())
+}
+
 /// gets the index of a certain sub-string
 /// the two references need to reference the same
 /// original String so that function works
@@ -29,9 +74,39 @@ pub fn pt_to_px(pt: f64, dpi: u16) -> usize {
     (pt * dpi as f64 / INCHES_PER_POINT) as usize
 }
 
-#[allow(dead_code)]
+/// the fraction of the full length the length takes up
+fn length_to_scale(length: config::Length, full: Pt, dpi: u16) -> f64 {
+    let pt = match length {
+        config::Length::Relative(scale) => return scale,
+        config::Length::Mm(mm) => Pt::from(Mm(mm)),
+        config::Length::Pt(pt) => Pt(pt),
+        config::Length::Px(px) => Pt(px * INCHES_PER_POINT / dpi as f64),
+    };
+
+    pt.0 / full.0
+}
+
+/// converts the lengths of the rectangle into
+/// fractions of an area with the given size
+pub fn scale_rect(
+    r: config::Rectangle<config::Length>,
+    size: (Pt, Pt),
+    dpi: u16,
+) -> config::Rectangle<f64> {
+    let scale = |p: config::Point<config::Length>| config::Point {
+        x: length_to_scale(p.x, size.0, dpi),
+        y: length_to_scale(p.y, size.1, dpi),
+    };
+
+    config::Rectangle {
+        orig: scale(r.orig),
+        size: scale(r.size),
+    }
+}
+
 /// different ways how the size of an pdf document
 /// can be described. Here it is always (x, y)
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Size {
     Mm(f64, f64),
     Px(usize, usize),
@@ -41,10 +116,10 @@ pub enum Size {
 impl Size {
     /// converts the size enum to a tuple
     /// with the size in Mm
-    pub fn to_mm(&self, dpi: u16) -> (Mm, Mm) {
+    pub fn to_mm(self, dpi: u16) -> (Mm, Mm) {
         let px_to_mm = |x| Mm::from(Px(x).into_pt(dpi as f64));
 
-        match *self {
+        match self {
             Size::Mm(x, y) => (Mm(x), Mm(y)),
             Size::Pt(x, y) => (Pt(x).into(), Pt(y).into()),
             Size::Px(x, y) => (px_to_mm(x), px_to_mm(y)),
@@ -52,70 +127,252 @@ impl Size {
     }
 }
 
-/// a simple wrapper around a rusttype font
+/// a glyph positioned by the shaper, the values are in font units
+pub struct Glyph {
+    pub id: u16,
+    /// how far the next glyph is moved to the right
+    pub advance: i32,
+    /// how far the glyph itself is moved (x, y)
+    pub offset: (i32, i32),
+    /// the byte index of the first char of the glyph inside the text
+    pub cluster: usize,
+}
+
+/// a font used for shaping text (ligatures, kerning, complex scripts)
 /// with metadata to scale it and get the right line height for that font
-pub struct RtFont<'a> {
-    inner: rusttype::Font<'a>,
-    scale: rusttype::Scale,
-    line_height: Pt,
-}
-
-impl<'a> RtFont<'a> {
-    /// wraps the given font into a RtFont
-    pub fn from_rt(font: rusttype::Font<'a>) -> Self {
-        let v_metrics = font.v_metrics_unscaled();
-        let line_height = (v_metrics.ascent - v_metrics.descent/*+ v_metrics.line_gap*/)
-            / font.units_per_em() as f32;
-        Self {
-            inner: font,
-            scale: rusttype::Scale::uniform(line_height),
-            line_height: Pt(line_height as f64),
-        }
+pub struct ShapingFont<'a> {
+    face: rustybuzz::Face<'a>,
+    data: &'a [u8],
+    /// the index of the face inside a font collection
+    index: u32,
+    /// the glyphs written into the pdf with the char they stand for
+    used: RefCell<HashMap<u16, String>>,
+    pub line_height: Pt,
+}
+
+impl<'a> ShapingFont<'a> {
+    /// parses the face at the index of the font data,
+    /// None if it isn't a valid font
+    pub fn from_data(data: &'a [u8], index: u32) -> Option<Self> {
+        let face = rustybuzz::Face::from_slice(data, index)?;
+
+        let line_height = (face.ascender() - face.descender()) as f64 / face.units_per_em() as f64;
+        Some(Self {
+            face,
+            data,
+            index,
+            used: Default::default(),
+            line_height: Pt(line_height),
+        })
     }
 
-    /// gets the width of every char (glyph) in the iterator + kerning from the current and
-    /// the last glyph
-    pub fn text_width<'b, I>(&'b self, font_size: f32, text: I) -> impl Iterator<Item = f32> + 'b
-    where
-        I: Iterator<Item = char> + 'b,
-    {
-        let rt_font = &self.inner;
-        rt_font
-            .glyphs_for(text)
-            .scan(None, move |last: &mut Option<rusttype::GlyphId>, g| {
-                let kerning = if let Some(last) = last {
-                    rt_font.pair_kerning(self.scale, *last, g.id())
+    /// remembers that the glyphs shaped from the text are written into the pdf
+    /// together with the text they stand for. The glyphs of a cluster get one
+    /// of its chars each, the last one all that are left, like a ligature
+    pub fn mark_used(&self, text: &str, glyphs: &[Glyph], rtl: bool) {
+        let mut starts: Vec<_> = glyphs.iter().map(|g| g.cluster).collect();
+        starts.sort_unstable();
+        starts.dedup();
+
+        let mut used = self.used.borrow_mut();
+        // the glyphs of a cluster follow each other in visual order
+        for cluster in glyphs.chunk_by(|a, b| a.cluster == b.cluster) {
+            let start = cluster[0].cluster;
+            let end = starts
+                .get(starts.partition_point(|s| *s <= start))
+                .map_or(text.len(), |s| *s);
+            // removed soft hyphens are part of the cluster before them
+            let chars: Vec<_> = text[start..end]
+                .chars()
+                .filter(|c| *c != SOFT_HYPHEN)
+                .collect();
+
+            let mut ids: Vec<_> = cluster.iter().map(|g| g.id).collect();
+            if rtl {
+                ids.reverse();
+            }
+            for (i, id) in ids.iter().enumerate() {
+                let part: String = if i + 1 == ids.len() {
+                    chars.iter().skip(i).collect()
                 } else {
-                    0.0
+                    chars.get(i).map(|c| c.to_string()).unwrap_or_default()
                 };
 
-                *last = Some(g.id());
-                // gets the width of the glyph
-                let width = self.get_width(font_size, g.id());
+                let text = used.entry(*id).or_default();
+                if text.is_empty() {
+                    *text = part;
+                }
+            }
+        }
+    }
 
-                // the total width is the glyph itself and also the space since the last glyph
-                Some(kerning + width)
+    /// the font data with only the used glyphs
+    /// together with the metrics of these glyphs
+    pub fn subset(&self) -> (Vec<u8>, UsedGlyphs) {
+        let glyphs = self
+            .used
+            .borrow()
+            .iter()
+            .map(|(id, text)| {
+                let glyph = rustybuzz::ttf_parser::GlyphId(*id);
+                let metrics = GlyphMetrics {
+                    width: self.face.glyph_hor_advance(glyph).unwrap_or(0) as u32,
+                    height: self
+                        .face
+                        .glyph_bounding_box(glyph)
+                        .map_or(0, |b| b.height().max(0) as u32),
+                };
+                (*id, (text.clone(), metrics))
             })
+            .collect();
+
+        let used = UsedGlyphs {
+            metrics: FontMetrics {
+                ascent: self.face.ascender(),
+                descent: self.face.descender(),
+                units_per_em: self.face.units_per_em() as u16,
+            },
+            glyphs,
+        };
+        (super::subset::subset(self.data, self.index, &used), used)
+    }
+
+    /// if the font has a glyph for the char
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.face.glyph_index(c).is_some()
     }
 
-    /// get the width of a glyph at a certain font_size
-    pub fn get_width<G: rusttype::IntoGlyphId>(&self, font_size: f32, glyph: G) -> f32 {
-        self.inner
-            .glyph(glyph)
-            .scaled(self.scale)
-            .h_metrics()
-            .advance_width
-            * font_size
+    /// the factor from font units to points at the font size
+    pub fn scale(&self, font_size: f32) -> f32 {
+        font_size / self.face.units_per_em() as f32
+    }
+
+    /// shapes the text into glyphs in visual order, soft hyphens are removed.
+    /// Without a direction (rtl) it's guessed from the script
+    pub fn shape(&self, text: &str, rtl: Option<bool>) -> Vec<Glyph> {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        match rtl {
+            Some(true) => buffer.set_direction(rustybuzz::Direction::RightToLeft),
+            Some(false) => buffer.set_direction(rustybuzz::Direction::LeftToRight),
+            None => (),
+        }
+        buffer.guess_segment_properties();
+        buffer.set_flags(rustybuzz::BufferFlags::REMOVE_DEFAULT_IGNORABLES);
+
+        let shaped = rustybuzz::shape(&self.face, &[], buffer);
+        shaped
+            .glyph_infos()
+            .iter()
+            .zip(shaped.glyph_positions())
+            .map(|(info, pos)| Glyph {
+                id: info.glyph_id as u16,
+                advance: pos.x_advance,
+                offset: (pos.x_offset, pos.y_offset),
+                cluster: info.cluster as usize,
+            })
+            .collect()
+    }
+
+    /// the width of the shaped text at a certain font_size
+    pub fn text_width(&self, font_size: f32, text: &str) -> f32 {
+        let advance: i32 = self.shape(text, None).iter().map(|g| g.advance).sum();
+        advance as f32 * self.scale(font_size)
+    }
+
+    /// the width printpdf writes into the pdf for the glyph,
+    /// in thousandths of the font size
+    pub fn pdf_width(&self, glyph: u16) -> f64 {
+        let scale: f64 = 1000.0 / self.face.units_per_em() as f64;
+        let advance = self
+            .face
+            .glyph_hor_advance(rustybuzz::ttf_parser::GlyphId(glyph))
+            .unwrap_or(0);
+
+        (advance as f64 * scale).trunc()
     }
 }
 
 use super::TextArgs;
-use arrayvec::ArrayVec;
 
 /// data for drawing individual lines
 pub struct LineData {
+    pub start_index: usize,
     pub end_index: usize,
     pub width: f32,
+    /// index of the paragraph the line belongs to
+    pub paragraph: usize,
+    /// the line was broken at a soft hyphen,
+    /// so a hyphen needs to be drawn at its end
+    pub hyphen: bool,
+    /// the paragraph of the line is written right-to-left
+    pub rtl: bool,
+}
+
+/// a word with its position inside the text
+pub struct Word {
+    pub start: usize,
+    pub end: usize,
+    pub width: f32,
+    /// width of the whitespace behind the word
+    pub space: f32,
+    /// width of the hyphen drawn if the line
+    /// is broken behind the word (ends with a soft hyphen)
+    pub hyphen: Option<f32>,
+}
+
+/// invisible unless a line is broken at it
+pub const SOFT_HYPHEN: char = '\u{ad}';
+
+/// inserts soft hyphens between the syllables of every word,
+/// words which already contain soft hyphens are kept as they are
+pub fn hyphenate(text: &str, lang: hypher::Lang) -> String {
+    let is_word_char = |c: char| c.is_alphabetic() || c == SOFT_HYPHEN;
+
+    let mut hyphenated = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_word_char) {
+        hyphenated.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        if word.contains(SOFT_HYPHEN) {
+            hyphenated.push_str(word);
+        } else {
+            hyphenated.push_str(&hypher::hyphenate(word, lang).join("\u{ad}"));
+        }
+        rest = &rest[end..];
+    }
+
+    hyphenated.push_str(rest);
+    hyphenated
+}
+
+/// a text run together with the loaded fonts
+pub struct LoadedRun<'a> {
+    /// the byte range inside the joined text of all runs
+    pub range: std::ops::Range<usize>,
+    pub pdf_font: &'a printpdf::IndirectFontRef,
+    pub font: &'a ShapingFont<'static>,
+    pub color: Option<config::Color>,
+    /// extra space after every glyph, in multiples of the font size
+    pub letter_spacing: f32,
+}
+
+impl<'a> LoadedRun<'a> {
+    /// the width of a part of the run with the letter spacing
+    pub fn width(&self, part: &str, font_size: f32) -> f32 {
+        if self.letter_spacing == 0.0 {
+            return self.font.text_width(font_size, part);
+        }
+
+        let glyphs = self.font.shape(part, None);
+        let advance: i32 = glyphs.iter().map(|g| g.advance).sum();
+        let spaced = glyphs.iter().filter(|g| g.advance != 0).count();
+        advance as f32 * self.font.scale(font_size)
+            + spaced as f32 * self.letter_spacing * font_size
+    }
 }
 
 /// data to better calculate the beginning
@@ -123,25 +380,64 @@ pub struct LineData {
 pub struct PositionArgs<'a> {
     text_args: &'a TextArgs<'a>,
     pub line_height: f64,
-    lines: &'a ArrayVec<LineData, 64>,
+    /// the space between two paragraphs
+    paragraph_space: f64,
+    lines: &'a [LineData],
 }
 
 impl<'a> PositionArgs<'a> {
     /// bundles the arguments into a PositionArgs struct together
-    pub fn new(
-        args: &'a TextArgs<'a>,
-        lines: &'a ArrayVec<LineData, 64>,
-        font: &RtFont<'_>,
-    ) -> Self {
+    pub fn new(args: &'a TextArgs<'a>, lines: &'a [LineData], font: &ShapingFont<'_>) -> Self {
         Self {
             lines,
-            line_height: font.line_height.0 * args.font_size,
+            line_height: font.line_height.0 * args.font_size * args.line_spacing,
+            paragraph_space: args.paragraph_spacing * args.font_size,
             text_args: args,
         }
     }
 
-    /// calculates the position a certain line should be drawn at
-    pub fn get_position(&self, line_idx: usize) -> config::Point<Mm> {
+    /// the number of paragraph breaks before the line
+    fn breaks_before(&self, line_idx: usize) -> usize {
+        self.lines[line_idx].paragraph - self.lines[0].paragraph
+    }
+
+    /// the height of all lines together
+    pub fn height(&self) -> f64 {
+        self.height_of(self.lines.len())
+    }
+
+    /// the height of the first n lines
+    pub fn height_of(&self, n: usize) -> f64 {
+        match n {
+            0 => 0.0,
+            n => {
+                n as f64 * self.line_height
+                    + self.breaks_before(n - 1) as f64 * self.paragraph_space
+            }
+        }
+    }
+
+    /// the space added to every whitespace of the line (gaps),
+    /// so that justified lines fill the whole width of the area
+    pub fn justify_space(&self, line_idx: usize, gaps: usize) -> f64 {
+        let line = &self.lines[line_idx];
+        // the last line of a paragraph keeps its normal spacing
+        let last = self
+            .lines
+            .get(line_idx + 1)
+            .is_none_or(|next| next.paragraph != line.paragraph);
+
+        let justify = self.text_args.orientation.horizontal == config::HorOrientation::Justify;
+        if !justify || last || gaps == 0 {
+            return 0.0;
+        }
+
+        (self.text_args.area.0.size.x.0 - line.width as f64).max(0.0) / gaps as f64
+    }
+
+    /// calculates the position a certain line with
+    /// the given number of gaps should be drawn at
+    pub fn get_position(&self, line_idx: usize, gaps: usize) -> config::Point<Mm> {
         let orientation = self.text_args.orientation;
         let area = &self.text_args.area.0;
         let size = area.size;
@@ -149,17 +445,26 @@ impl<'a> PositionArgs<'a> {
         use config::HorOrientation as Hor;
         use config::VertOrientation as Vert;
 
-        let y = match orientation.vertical {
-            Vert::Top => size.y.0 - (line_idx + 1) as f64 * self.line_height,
-            Vert::Middle => size.y.0 / 2.0 - line_idx as f64 * self.line_height,
-            Vert::Bottom => (self.lines.len() - (line_idx + 1)) as f64 * self.line_height,
+        // the top of the whole text
+        let top = match orientation.vertical {
+            Vert::Top => size.y.0,
+            Vert::Middle => (size.y.0 + self.height()) / 2.0,
+            Vert::Bottom => self.height(),
         };
+        let y = top
+            - (line_idx + 1) as f64 * self.line_height
+            - self.breaks_before(line_idx) as f64 * self.paragraph_space;
 
-        let width = self.lines[line_idx].width;
-        let x = match orientation.horizontal {
-            Hor::Left => 0.0,
-            Hor::Middle => (size.x.0 - width as f64) / 2.0,
-            Hor::Right => size.x.0 - width as f64,
+        let line = &self.lines[line_idx];
+        let width = line.width as f64;
+        // stretched lines fill the whole width, the others of
+        // right-to-left paragraphs start on the right
+        let stretched = self.justify_space(line_idx, gaps) > 0.0;
+        let x = match (&orientation.horizontal, line.rtl) {
+            (Hor::Justify, _) if stretched => 0.0,
+            (Hor::Left | Hor::Justify, false) | (Hor::Right, true) => 0.0,
+            (Hor::Middle, _) => (size.x.0 - width) / 2.0,
+            (Hor::Right, false) | (Hor::Left | Hor::Justify, true) => size.x.0 - width,
         };
 
         let pos = config::Point { x: Pt(x), y: Pt(y) } + area.orig;